@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use crate::trace::{export::TraceFormat, TraceExportConfig};
+
+/// The command line arguments of the game.
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub trace_export: Option<TraceExportConfig>,
+    // Directories of extra level packs, each with its own manifest.
+    pub level_packs: Vec<PathBuf>,
+    // Whether to check the level files and exit, rather than start the game.
+    pub validate_levels: bool,
+}
+
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut trace_path = None;
+        let mut trace_format = TraceFormat::Otlp;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--export-traces" => {
+                    trace_path = Some(args.next().ok_or("Missing path for `--export-traces`.")?);
+                }
+                "--trace-format" => {
+                    trace_format = args
+                        .next()
+                        .ok_or("Missing format for `--trace-format`.")?
+                        .parse()?;
+                }
+                "--level-pack" => {
+                    parsed.level_packs.push(
+                        args.next()
+                            .ok_or("Missing directory for `--level-pack`.")?
+                            .into(),
+                    );
+                }
+                "validate-levels" => parsed.validate_levels = true,
+                _ => return Err(format!("Unknown argument `{arg}`.")),
+            }
+        }
+
+        parsed.trace_export = trace_path.map(|path| TraceExportConfig {
+            path: path.into(),
+            format: trace_format,
+        });
+
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn should_parse_trace_export_args() {
+        assert_eq!(Ok(Args::default()), parse(&[]));
+        assert_eq!(
+            Some(TraceExportConfig {
+                path: "out.json".into(),
+                format: TraceFormat::Jaeger
+            }),
+            parse(&["--trace-format", "jaeger", "--export-traces", "out.json"])
+                .unwrap()
+                .trace_export
+        );
+        assert!(parse(&["--export-traces"]).is_err());
+        assert!(parse(&["--trace-format", "zipkin"]).is_err());
+    }
+
+    #[test]
+    fn should_parse_every_level_pack() {
+        assert_eq!(
+            vec![
+                PathBuf::from("packs/onboarding"),
+                PathBuf::from("/srv/levels")
+            ],
+            parse(&[
+                "--level-pack",
+                "packs/onboarding",
+                "--level-pack",
+                "/srv/levels"
+            ])
+            .unwrap()
+            .level_packs
+        );
+        assert!(parse(&["--level-pack"]).is_err());
+    }
+
+    #[test]
+    fn should_parse_validate_levels_command() {
+        let args = parse(&["validate-levels", "--level-pack", "packs/onboarding"]).unwrap();
+
+        assert!(args.validate_levels);
+        assert_eq!(vec![PathBuf::from("packs/onboarding")], args.level_packs);
+        assert!(!parse(&[]).unwrap().validate_levels);
+    }
+}
@@ -0,0 +1,510 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::{ListenedEvent, PointerButton, Up};
+use bevy_prototype_lyon::prelude::Stroke;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_state::{AppState, GameMode},
+    grid::{ConnectedNodeConnectionLine, ConnectionLineClickEvent},
+    level::CurrentLevel,
+    message::{
+        move_messages, Message, MessageArrivedEvent, MessageArrivedEventSet, MessageComponent,
+        MessageFailedEvent, Response, SendMessageEvent,
+    },
+    node::{
+        database::Database,
+        server::{Server, ServerState},
+        NodeName, NodeType, SystemNode,
+    },
+    simulation::{tick_simulation_clock, SimulationClock},
+};
+
+pub struct ChaosPlugin;
+
+impl Plugin for ChaosPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Faults>();
+        app.init_resource::<ChaosMenu>();
+
+        app.add_systems((clear_faults, close_chaos_menu).in_schedule(OnEnter(AppState::Simulate)));
+        app.add_systems((clear_faults, close_chaos_menu).in_schedule(OnEnter(AppState::Edit)));
+
+        // Faults are applied before messages move, so a message never arrives at a node that
+        // goes down in the same frame.
+        app.add_systems(
+            (apply_chaos_timeline, apply_faults)
+                .chain()
+                .after(tick_simulation_clock)
+                .before(move_messages)
+                .in_set(OnUpdate(AppState::Simulate)),
+        );
+
+        app.add_system(
+            fail_messages
+                .run_if(on_event::<MessageFailedEvent>())
+                .after(move_messages)
+                .before(MessageArrivedEventSet),
+        );
+
+        app.add_system(
+            open_chaos_menu
+                .run_if(in_state(GameMode::Sandbox))
+                .in_set(OnUpdate(AppState::Simulate)),
+        );
+
+        app.add_systems(
+            (show_down_nodes, show_faulty_connections)
+                .distributive_run_if(resource_changed::<Faults>()),
+        );
+    }
+}
+
+/// A failure scripted by a level, in effect from `at_ms` of simulation time until `until_ms`, or
+/// until the simulation finishes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChaosEvent {
+    pub at_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until_ms: Option<u64>,
+    pub fault: Fault,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Fault {
+    // The named Server or Database stops answering. A Server loses its state and every request
+    // it was handling.
+    Crash(String),
+    // Each message on the connection between the two named nodes is lost with the given chance.
+    DropMessages(String, String, f32),
+    // The named Database refuses to save or delete documents.
+    ReadOnly(String),
+}
+
+impl ChaosEvent {
+    pub fn is_active(&self, now: Duration) -> bool {
+        let now = now.as_millis() as u64;
+
+        self.at_ms <= now && self.until_ms.is_none_or(|until_ms| now < until_ms)
+    }
+}
+
+#[derive(Default, Debug, PartialEq)]
+struct FaultSet {
+    down: HashSet<Entity>,
+    read_only: HashSet<Entity>,
+    // The chance of a message between two nodes being lost, in either direction.
+    drop_rates: HashMap<(Entity, Entity), f32>,
+    // The extra time messages wait at a node before it receives them.
+    latencies: HashMap<Entity, Duration>,
+}
+
+impl FaultSet {
+    // The faults of the events active at the given time. Faults of unknown nodes are ignored.
+    fn from_timeline(
+        chaos: &[ChaosEvent],
+        now: Duration,
+        find_node: impl Fn(&str) -> Option<Entity>,
+    ) -> Self {
+        let mut faults = Self::default();
+
+        for event in chaos.iter().filter(|event| event.is_active(now)) {
+            match &event.fault {
+                Fault::Crash(name) => faults.down.extend(find_node(name)),
+                Fault::ReadOnly(name) => faults.read_only.extend(find_node(name)),
+                Fault::DropMessages(a, b, rate) => {
+                    if let (Some(a), Some(b)) = (find_node(a), find_node(b)) {
+                        let drop_rate = faults.drop_rates.entry((a, b)).or_default();
+                        *drop_rate = drop_rate.max(*rate);
+                    }
+                }
+            }
+        }
+
+        faults
+    }
+
+    fn drop_rate(&self, a: Entity, b: Entity) -> f32 {
+        self.drop_rates
+            .get(&(a, b))
+            .or_else(|| self.drop_rates.get(&(b, a)))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// The faults in effect at the current simulation time: those of the chaos timeline of the level,
+/// and those injected by hand in the Sandbox.
+#[derive(Resource, Default, Debug)]
+pub struct Faults {
+    timeline: FaultSet,
+    manual: FaultSet,
+    // The number of messages to each node still to be lost.
+    drops_remaining: HashMap<Entity, u32>,
+}
+
+impl Faults {
+    pub fn is_down(&self, node: Entity) -> bool {
+        self.timeline.down.contains(&node) || self.manual.down.contains(&node)
+    }
+
+    pub fn is_read_only(&self, node: Entity) -> bool {
+        self.timeline.read_only.contains(&node) || self.manual.read_only.contains(&node)
+    }
+
+    pub fn drop_rate(&self, a: Entity, b: Entity) -> f32 {
+        self.timeline
+            .drop_rate(a, b)
+            .max(self.manual.drop_rate(a, b))
+    }
+
+    pub fn latency(&self, node: Entity) -> Duration {
+        self.manual
+            .latencies
+            .get(&node)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn drops_remaining(&self, node: Entity) -> u32 {
+        self.drops_remaining.get(&node).copied().unwrap_or_default()
+    }
+
+    /// Whether the next message to the node is lost, counting it towards the messages to drop.
+    pub fn take_drop(&mut self, node: Entity) -> bool {
+        match self.drops_remaining.get_mut(&node) {
+            Some(remaining) if *remaining > 0 => {
+                *remaining -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn set_down(&mut self, node: Entity, down: bool) {
+        if down {
+            self.manual.down.insert(node);
+        } else {
+            self.manual.down.remove(&node);
+        }
+    }
+
+    pub fn set_latency(&mut self, node: Entity, latency: Duration) {
+        self.manual.latencies.insert(node, latency);
+    }
+
+    /// The chance of a message being lost on a connection, as set by hand.
+    pub fn manual_drop_rate(&self, a: Entity, b: Entity) -> f32 {
+        self.manual.drop_rate(a, b)
+    }
+
+    pub fn set_drop_rate(&mut self, a: Entity, b: Entity, rate: f32) {
+        self.manual.drop_rates.remove(&(b, a));
+        self.manual.drop_rates.insert((a, b), rate);
+    }
+
+    pub fn drop_next(&mut self, node: Entity, count: u32) {
+        *self.drops_remaining.entry(node).or_default() += count;
+    }
+}
+
+fn clear_faults(mut faults: ResMut<Faults>) {
+    *faults = Faults::default();
+}
+
+fn apply_chaos_timeline(
+    current_level: CurrentLevel,
+    simulation_clock: Res<SimulationClock>,
+    mut faults: ResMut<Faults>,
+    nodes: Query<(Entity, &NodeName)>,
+) {
+    let Some((_, level)) = current_level.get() else {
+        return;
+    };
+
+    let find_node = |name: &str| {
+        nodes
+            .iter()
+            .find(|(_, node_name)| node_name.0 == name)
+            .map(|(entity, _)| entity)
+    };
+
+    let timeline = FaultSet::from_timeline(&level.chaos, simulation_clock.elapsed(), find_node);
+
+    if timeline != faults.timeline {
+        faults.timeline = timeline;
+    }
+}
+
+// Crashes and recovers nodes as they go down and come back up.
+pub fn apply_faults(
+    faults: Res<Faults>,
+    mut servers: Query<(Entity, &mut Server)>,
+    mut databases: Query<(Entity, &mut Database)>,
+    mut events: EventWriter<SendMessageEvent>,
+) {
+    if !faults.is_changed() {
+        return;
+    }
+
+    for (entity, mut server) in servers.iter_mut() {
+        match (server.state == ServerState::Down, faults.is_down(entity)) {
+            (false, true) => {
+                // Every open connection to the server is closed with an error.
+                for (sender, span) in server.crash() {
+                    events.send(SendMessageEvent {
+                        sender: entity,
+                        recipients: vec![sender],
+                        message: Message::Response(Response::service_unavailable()),
+                        span,
+                    });
+                }
+            }
+            (true, false) => server.recover(),
+            _ => {}
+        }
+    }
+
+    for (entity, mut database) in databases.iter_mut() {
+        match (database.is_down(), faults.is_down(entity)) {
+            (false, true) => database.crash(),
+            (true, false) => database.recover(),
+            _ => {}
+        }
+
+        database.read_only = faults.is_read_only(entity);
+    }
+}
+
+/// The node or connection whose faults are being changed by hand in the Sandbox.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChaosTarget {
+    Node(Entity),
+    Connection(Entity, Entity),
+}
+
+#[derive(Resource)]
+pub struct ChaosMenu {
+    pub target: Option<ChaosTarget>,
+    // The number of messages to drop, as entered in the menu.
+    pub drop_count: u32,
+}
+
+impl Default for ChaosMenu {
+    fn default() -> Self {
+        Self {
+            target: None,
+            drop_count: 1,
+        }
+    }
+}
+
+fn close_chaos_menu(mut chaos_menu: ResMut<ChaosMenu>) {
+    chaos_menu.target = None;
+}
+
+// Right-clicking a node or connection while a Sandbox simulation runs opens the chaos menu for it.
+fn open_chaos_menu(
+    mut node_events: EventReader<ListenedEvent<Up>>,
+    mut line_events: EventReader<ConnectionLineClickEvent>,
+    lines: Query<&ConnectedNodeConnectionLine>,
+    mut chaos_menu: ResMut<ChaosMenu>,
+) {
+    for event in node_events.iter() {
+        if matches!(event.button, PointerButton::Secondary) {
+            chaos_menu.target = Some(ChaosTarget::Node(event.target));
+        }
+    }
+
+    for ConnectionLineClickEvent(event) in line_events.iter() {
+        if !matches!(event.button, PointerButton::Secondary) {
+            continue;
+        }
+
+        if let Ok(ConnectedNodeConnectionLine(a, b)) = lines.get(event.target) {
+            chaos_menu.target = Some(ChaosTarget::Connection(*a, *b));
+        }
+    }
+}
+
+// Answers the messages that could not be delivered, so that nothing waits forever for them.
+fn fail_messages(
+    mut failed_events: EventReader<MessageFailedEvent>,
+    mut send_events: EventWriter<SendMessageEvent>,
+    mut arrived_events: EventWriter<MessageArrivedEvent>,
+    faults: Res<Faults>,
+    nodes: Query<(&NodeName, &NodeType)>,
+) {
+    for MessageFailedEvent(message) in failed_events.iter() {
+        let recipient_down = faults.is_down(message.recipient);
+
+        let reason = match nodes.get(message.recipient) {
+            Ok((node_name, node_type)) if recipient_down => {
+                format!("{} is down.", node_name.label(node_type, message.recipient))
+            }
+            _ if recipient_down => "The node is down.".to_string(),
+            _ => "The message was lost.".to_string(),
+        };
+
+        let failure = match &message.message {
+            Message::Request(_) | Message::HealthCheck if recipient_down => {
+                Message::Response(Response {
+                    data: reason.into(),
+                    ..Response::service_unavailable()
+                })
+            }
+            Message::Request(_) | Message::HealthCheck => {
+                Message::Response(Response::gateway_timeout())
+            }
+            Message::DatabaseCall(_) => Message::DatabaseError(reason),
+            // A node that is down has already given up on the requests it was waiting on.
+            _ if recipient_down => continue,
+            Message::Response(_) => {
+                arrived_events.send(MessageArrivedEvent(MessageComponent {
+                    message: Message::Response(Response::gateway_timeout()),
+                    ..message.clone()
+                }));
+                continue;
+            }
+            Message::DatabaseAnswer(_) | Message::DatabaseError(_) => {
+                arrived_events.send(MessageArrivedEvent(MessageComponent {
+                    message: Message::DatabaseError(reason),
+                    ..message.clone()
+                }));
+                continue;
+            }
+        };
+
+        // The failure is sent back from where the message was going.
+        send_events.send(SendMessageEvent {
+            sender: message.recipient,
+            recipients: vec![message.sender],
+            message: failure,
+            span: message.span,
+        });
+    }
+}
+
+const DOWN_NODE_COLOR: Color = Color::rgb(1.0, 0.35, 0.35);
+
+fn show_down_nodes(
+    faults: Res<Faults>,
+    nodes: Query<(Entity, &Children), With<SystemNode>>,
+    mut sprites: Query<&mut Sprite>,
+) {
+    for (entity, children) in nodes.iter() {
+        let color = if faults.is_down(entity) {
+            DOWN_NODE_COLOR
+        } else {
+            Color::WHITE
+        };
+
+        for child in children.iter() {
+            if let Ok(mut sprite) = sprites.get_mut(*child) {
+                sprite.color = color;
+            }
+        }
+    }
+}
+
+fn show_faulty_connections(
+    faults: Res<Faults>,
+    mut lines: Query<(&ConnectedNodeConnectionLine, &mut Stroke)>,
+) {
+    for (ConnectedNodeConnectionLine(a, b), mut stroke) in lines.iter_mut() {
+        stroke.color = match faults.drop_rate(*a, *b) {
+            rate if rate >= 1.0 => Color::RED,
+            rate if rate > 0.0 => Color::ORANGE,
+            _ => Color::YELLOW,
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_apply_faults_of_active_events() {
+        let server1 = Entity::from_raw(1);
+        let server2 = Entity::from_raw(2);
+        let load_balancer = Entity::from_raw(3);
+
+        let find_node = |name: &str| match name {
+            "server1" => Some(server1),
+            "server2" => Some(server2),
+            "load-balancer" => Some(load_balancer),
+            _ => None,
+        };
+
+        let chaos = [
+            ChaosEvent {
+                at_ms: 3000,
+                until_ms: Some(8000),
+                fault: Fault::Crash("server2".to_string()),
+            },
+            ChaosEvent {
+                at_ms: 0,
+                until_ms: None,
+                fault: Fault::DropMessages("load-balancer".to_string(), "server1".to_string(), 0.2),
+            },
+            ChaosEvent {
+                at_ms: 0,
+                until_ms: None,
+                fault: Fault::Crash("cache".to_string()),
+            },
+        ];
+
+        let faults_at = |ms| Faults {
+            timeline: FaultSet::from_timeline(&chaos, Duration::from_millis(ms), find_node),
+            ..Default::default()
+        };
+
+        assert!(!faults_at(2999).is_down(server2));
+        assert!(faults_at(3000).is_down(server2));
+        assert!(!faults_at(8000).is_down(server2));
+        assert_eq!(0.2, faults_at(0).drop_rate(server1, load_balancer));
+        assert_eq!(0.0, faults_at(0).drop_rate(server1, server2));
+    }
+
+    #[test]
+    fn should_combine_manual_faults_with_the_timeline() {
+        let server = Entity::from_raw(1);
+        let database = Entity::from_raw(2);
+
+        let chaos = [ChaosEvent {
+            at_ms: 0,
+            until_ms: None,
+            fault: Fault::DropMessages("server".to_string(), "database".to_string(), 0.2),
+        }];
+
+        let mut faults = Faults {
+            timeline: FaultSet::from_timeline(&chaos, Duration::ZERO, |name| match name {
+                "server" => Some(server),
+                "database" => Some(database),
+                _ => None,
+            }),
+            ..Default::default()
+        };
+
+        faults.set_drop_rate(database, server, 0.5);
+        faults.set_down(database, true);
+        faults.drop_next(server, 2);
+
+        assert_eq!(0.5, faults.drop_rate(server, database));
+        assert!(faults.is_down(database));
+        assert!(faults.take_drop(server));
+        assert!(faults.take_drop(server));
+        assert!(!faults.take_drop(server));
+
+        faults.set_drop_rate(server, database, 0.0);
+        faults.set_down(database, false);
+
+        assert_eq!(0.2, faults.drop_rate(database, server));
+        assert!(!faults.is_down(database));
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::game_state::AppState;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConsoleLog>();
+        app.add_system(clear_console_log.in_schedule(OnEnter(AppState::Simulate)));
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, strum::Display)]
+pub enum LogLevel {
+    Log,
+    Warn,
+    Error,
+}
+
+/// A single line of output written by a node's handler code, e.g. via `console.log`.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConsoleMessage {
+    pub level: LogLevel,
+    pub message: String,
+}
+
+impl ConsoleMessage {
+    pub fn warn(message: String) -> Self {
+        Self {
+            level: LogLevel::Warn,
+            message,
+        }
+    }
+
+    pub fn error(message: String) -> Self {
+        Self {
+            level: LogLevel::Error,
+            message,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub node: Entity,
+    pub time: Duration,
+    pub trace_id: Uuid,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+/// All console output of the current simulation, in the order it was written.
+#[derive(Resource, Default)]
+pub struct ConsoleLog {
+    entries: Vec<LogEntry>,
+}
+
+impl ConsoleLog {
+    pub fn push(&mut self, node: Entity, time: Duration, trace_id: Uuid, message: ConsoleMessage) {
+        self.entries.push(LogEntry {
+            node,
+            time,
+            trace_id,
+            level: message.level,
+            message: message.message,
+        });
+    }
+
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+}
+
+fn clear_console_log(mut console_log: ResMut<ConsoleLog>) {
+    console_log.entries.clear();
+}
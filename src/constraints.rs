@@ -0,0 +1,239 @@
+use std::collections::VecDeque;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    level::{Constraints, CurrentLevel},
+    node::{NodeConnections, NodeName, NodeType},
+};
+
+/// A node of a system, with the indices of the nodes it is connected to.
+pub struct GraphNode<'a> {
+    pub node_type: NodeType,
+    pub name: &'a str,
+    pub connections: Vec<usize>,
+}
+
+impl Constraints {
+    pub fn max_nodes(&self, node_type: &NodeType) -> Option<usize> {
+        match node_type {
+            NodeType::Client => self.max_clients,
+            NodeType::Server => self.max_servers,
+            NodeType::Database => self.max_databases,
+        }
+    }
+
+    /// Whether a system meets each of the constraints, with a description of the constraint.
+    pub fn check(&self, nodes: &[GraphNode]) -> Vec<(bool, String)> {
+        let mut results = vec![];
+
+        for node_type in [NodeType::Client, NodeType::Server, NodeType::Database] {
+            if let Some(max) = self.max_nodes(&node_type) {
+                let count = nodes.iter().filter(|n| n.node_type == node_type).count();
+
+                results.push((
+                    count <= max,
+                    format!("At most {max} {node_type}s, {count} used"),
+                ));
+            }
+        }
+
+        if let Some(min_hops) = self.min_database_hops {
+            let hops = database_hops(nodes);
+            let passed = hops.is_none_or(|hops| hops >= min_hops);
+
+            let mut msg =
+                format!("Clients reach Databases through at least {min_hops} connections");
+
+            if let (false, Some(hops)) = (passed, hops) {
+                msg.push_str(&format!(", found {hops}"));
+            }
+
+            results.push((passed, msg));
+        }
+
+        if let Some(expected) = self.public_servers {
+            let count = nodes
+                .iter()
+                .filter(|node| {
+                    node.node_type == NodeType::Server
+                        && node
+                            .connections
+                            .iter()
+                            .any(|other| nodes[*other].node_type == NodeType::Client)
+                })
+                .count();
+
+            let mut msg = format!("Exactly {expected} Servers connected to Clients");
+
+            if count != expected {
+                msg.push_str(&format!(", found {count}"));
+            }
+
+            results.push((count == expected, msg));
+        }
+
+        for (a, b) in self.required_connections.iter() {
+            results.push((
+                is_connected(nodes, a, b),
+                format!("`{a}` connected to `{b}`"),
+            ));
+        }
+
+        for (a, b) in self.forbidden_connections.iter() {
+            results.push((
+                !is_connected(nodes, a, b),
+                format!("`{a}` not connected to `{b}`"),
+            ));
+        }
+
+        results
+    }
+}
+
+fn is_connected(nodes: &[GraphNode], a: &str, b: &str) -> bool {
+    nodes
+        .iter()
+        .filter(|node| node.name == a)
+        .any(|node| node.connections.iter().any(|other| nodes[*other].name == b))
+}
+
+// The fewest connections between any Client and any Database, if any Database can be reached.
+fn database_hops(nodes: &[GraphNode]) -> Option<usize> {
+    let mut distances: Vec<Option<usize>> = nodes
+        .iter()
+        .map(|node| (node.node_type == NodeType::Client).then_some(0))
+        .collect();
+
+    let mut queue: VecDeque<usize> = (0..nodes.len())
+        .filter(|idx| distances[*idx].is_some())
+        .collect();
+
+    while let Some(idx) = queue.pop_front() {
+        let distance = distances[idx].unwrap();
+
+        if nodes[idx].node_type == NodeType::Database {
+            return Some(distance);
+        }
+
+        for other in nodes[idx].connections.iter() {
+            if distances[*other].is_none() {
+                distances[*other] = Some(distance + 1);
+                queue.push_back(*other);
+            }
+        }
+    }
+
+    None
+}
+
+/// The constraints of the current level, checked against the current system.
+#[derive(SystemParam)]
+pub struct SystemConstraints<'w, 's> {
+    current_level: CurrentLevel<'w>,
+    nodes: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static NodeType,
+            &'static NodeName,
+            &'static NodeConnections,
+        ),
+    >,
+}
+
+impl<'w, 's> SystemConstraints<'w, 's> {
+    fn constraints(&self) -> Option<&Constraints> {
+        self.current_level
+            .get()
+            .and_then(|(_, level)| level.constraints.as_ref())
+    }
+
+    pub fn results(&self) -> Vec<(bool, String)> {
+        let Some(constraints) = self.constraints() else {
+            return vec![];
+        };
+
+        let entities: Vec<_> = self.nodes.iter().map(|(entity, ..)| entity).collect();
+
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|(_, node_type, node_name, connections)| GraphNode {
+                node_type: *node_type,
+                name: &node_name.0,
+                connections: connections
+                    .iter()
+                    .filter_map(|(other, _)| entities.iter().position(|e| e == other))
+                    .collect(),
+            })
+            .collect();
+
+        constraints.check(&nodes)
+    }
+
+    pub fn are_met(&self) -> bool {
+        self.results().iter().all(|(passed, _)| *passed)
+    }
+
+    /// Whether another node of the given type may be added.
+    pub fn can_add(&self, node_type: &NodeType) -> bool {
+        self.constraints()
+            .and_then(|constraints| constraints.max_nodes(node_type))
+            .is_none_or(|max| {
+                self.nodes
+                    .iter()
+                    .filter(|(_, other_type, ..)| *other_type == node_type)
+                    .count()
+                    < max
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn node<'a>(node_type: NodeType, name: &'a str, connections: &[usize]) -> GraphNode<'a> {
+        GraphNode {
+            node_type,
+            name,
+            connections: connections.to_vec(),
+        }
+    }
+
+    #[test]
+    fn should_check_node_counts_and_topology() {
+        let constraints = Constraints {
+            max_servers: Some(2),
+            min_database_hops: Some(3),
+            public_servers: Some(1),
+            required_connections: vec![("gateway".to_string(), "users-db".to_string())],
+            forbidden_connections: vec![("user".to_string(), "api".to_string())],
+            ..Default::default()
+        };
+
+        // user -> gateway -> users-db, with api also connected to the database.
+        let nodes = [
+            node(NodeType::Client, "user", &[1]),
+            node(NodeType::Server, "gateway", &[0, 3]),
+            node(NodeType::Server, "api", &[3]),
+            node(NodeType::Database, "users-db", &[1, 2]),
+        ];
+
+        assert_eq!(
+            vec![
+                (true, "At most 2 Servers, 2 used".to_string()),
+                (
+                    false,
+                    "Clients reach Databases through at least 3 connections, found 2".to_string()
+                ),
+                (true, "Exactly 1 Servers connected to Clients".to_string()),
+                (true, "`gateway` connected to `users-db`".to_string()),
+                (true, "`user` not connected to `api`".to_string()),
+            ],
+            constraints.check(&nodes)
+        );
+    }
+}
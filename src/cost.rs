@@ -0,0 +1,150 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    level::{CurrentLevel, Pricing},
+    metrics::Metrics,
+    node::{NodeConnections, NodeType},
+};
+
+impl Pricing {
+    pub fn node_cost(&self, node_type: &NodeType) -> u32 {
+        match node_type {
+            NodeType::Client => self.client,
+            NodeType::Server => self.server,
+            NodeType::Database => self.database,
+        }
+    }
+
+    /// The up front cost of the nodes and connections of a system.
+    pub fn infrastructure_cost<'a>(
+        &self,
+        node_types: impl Iterator<Item = &'a NodeType>,
+        num_connections: usize,
+    ) -> u32 {
+        node_types
+            .map(|node_type| self.node_cost(node_type))
+            .fold(0, u32::saturating_add)
+            .saturating_add(
+                self.connection
+                    .saturating_mul(saturating_u32(num_connections)),
+            )
+    }
+
+    pub fn request_cost(&self, num_requests: usize) -> u32 {
+        self.request.saturating_mul(saturating_u32(num_requests))
+    }
+}
+
+// Costs stop at the largest u32 rather than overflow, which is over any budget.
+fn saturating_u32(count: usize) -> u32 {
+    u32::try_from(count).unwrap_or(u32::MAX)
+}
+
+/// The cost of the current system under the current level's pricing, which is free when the
+/// level has no pricing.
+#[derive(SystemParam)]
+pub struct SystemCost<'w, 's> {
+    current_level: CurrentLevel<'w>,
+    nodes: Query<'w, 's, (&'static NodeType, &'static NodeConnections)>,
+    metrics: Res<'w, Metrics>,
+}
+
+impl<'w, 's> SystemCost<'w, 's> {
+    fn pricing(&self) -> Option<&Pricing> {
+        self.current_level
+            .get()
+            .and_then(|(_, level)| level.pricing.as_ref())
+    }
+
+    pub fn budget(&self) -> Option<u32> {
+        self.pricing()?;
+
+        self.current_level.get().and_then(|(_, level)| level.budget)
+    }
+
+    pub fn has_pricing(&self) -> bool {
+        self.pricing().is_some()
+    }
+
+    pub fn infrastructure_cost(&self) -> u32 {
+        // Every connection is stored on both of its nodes.
+        let num_connections = self
+            .nodes
+            .iter()
+            .map(|(_, connections)| connections.iter().count())
+            .sum::<usize>()
+            / 2;
+
+        self.pricing()
+            .map(|pricing| {
+                pricing.infrastructure_cost(
+                    self.nodes.iter().map(|(node_type, _)| node_type),
+                    num_connections,
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// The cost of the requests made so far in the current simulation.
+    pub fn request_cost(&self) -> u32 {
+        self.pricing()
+            .map(|pricing| pricing.request_cost(self.metrics.requests_sent()))
+            .unwrap_or_default()
+    }
+
+    pub fn total_cost(&self) -> u32 {
+        self.infrastructure_cost()
+            .saturating_add(self.request_cost())
+    }
+
+    pub fn is_infrastructure_within_budget(&self) -> bool {
+        self.budget()
+            .is_none_or(|budget| self.infrastructure_cost() <= budget)
+    }
+
+    pub fn is_within_budget(&self) -> bool {
+        self.budget()
+            .is_none_or(|budget| self.total_cost() <= budget)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_price_nodes_by_type_and_every_connection() {
+        let pricing = Pricing {
+            client: 0,
+            server: 50,
+            database: 80,
+            connection: 5,
+            request: 1,
+        };
+
+        let node_types = [
+            NodeType::Client,
+            NodeType::Server,
+            NodeType::Server,
+            NodeType::Database,
+        ];
+
+        assert_eq!(
+            100 + 80 + 15,
+            pricing.infrastructure_cost(node_types.iter(), 3)
+        );
+        assert_eq!(12, pricing.request_cost(12));
+
+        let expensive = Pricing {
+            server: u32::MAX,
+            request: u32::MAX,
+            ..pricing
+        };
+
+        assert_eq!(
+            u32::MAX,
+            expensive.infrastructure_cost(node_types.iter(), 3)
+        );
+        assert_eq!(u32::MAX, expensive.request_cost(2));
+    }
+}
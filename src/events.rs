@@ -22,6 +22,8 @@ impl AddComponentEvent {
     }
 }
 
+// Payloads only live until the node is spawned, so are not worth boxing.
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone)]
 pub enum AddComponentPayload {
     Client(Client),
@@ -10,6 +10,7 @@ pub enum AppState {
     Validate,
     Simulate,
     SimulateFinish,
+    LevelEditor,
 }
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
@@ -1,37 +1,62 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::Duration,
+};
 
-use bevy::prelude::{App, EventWriter, Plugin, SystemSet};
+use bevy::prelude::{App, EventWriter, Plugin};
 use bevy_egui::{
-    egui::{self, text::LayoutJob, Color32, Context, TextFormat},
+    egui::{
+        self,
+        plot::{Legend, Line, Plot, PlotPoints},
+        text::LayoutJob,
+        Color32, Context, TextFormat,
+    },
     EguiContexts,
 };
 use bevy_mod_picking::selection::PickSelection;
 use egui_modal::Modal;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
 use strum::IntoEnumIterator;
+use uuid::Uuid;
 
 use crate::{
+    chaos::{apply_faults, ChaosMenu, ChaosTarget, Faults},
+    console::{ConsoleLog, LogLevel},
+    constraints::SystemConstraints,
+    cost::SystemCost,
     events::AddComponentEvent,
     game_state::{AppState, GameMode},
     grid::DeleteNodeEvent,
     level::{CurrentLevel, Level, LevelState},
+    level_editor::{EditorNode, LevelEditorState},
+    loading::LevelLoadErrors,
+    metrics::{Metrics, RequestStats, ServerSample},
     node::{
         client::{Client, HttpMethod, RequestConfig},
-        database::Database,
-        server::{Endpoint, Server},
-        Hostname, NodeName, NodeType, SystemNodeTrait,
+        database::{Database, Document},
+        rate_limit::{RateLimit, RateLimitAlgorithm, RateLimitKey},
+        server::{Endpoint, Middleware, Server, ServerState},
+        upstream::CircuitState,
+        Hostname, Locked, NodeConnections, NodeName, NodeType, SystemNodeTrait,
+    },
+    progress::Progress,
+    simulation::SimulationClock,
+    trace::{
+        export::{export_traces, write_traces, TraceFormat},
+        export_nodes, SpanOperation, SpanRecord, TraceStore,
     },
     GridSet, Handles, MainMenuSet,
 };
 
 use bevy::prelude::*;
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
-struct GameUiSystemSet;
-
 pub struct GameUiPlugin;
 
 impl Plugin for GameUiPlugin {
     fn build(&self, app: &mut App) {
+        app.add_system(level_load_errors_ui.in_set(OnUpdate(AppState::Loading)));
         app.add_system(main_menu_ui.in_set(MainMenuSet));
         app.add_system(level_select_ui.in_set(OnUpdate(AppState::LevelSelect)));
 
@@ -47,6 +72,34 @@ impl Plugin for GameUiPlugin {
 
         app.add_system(tools_ui.in_set(GridSet));
         app.add_system(bottom_panel_ui.in_set(GridSet).after(tools_ui));
+        app.add_system(console_log_ui.in_set(GridSet).after(bottom_panel_ui));
+        app.add_system(
+            trace_viewer_ui
+                .in_set(GridSet)
+                .after(console_log_ui)
+                .run_if(in_state(AppState::Simulate).or_else(in_state(AppState::SimulateFinish))),
+        );
+        app.add_system(
+            metrics_dashboard_ui
+                .in_set(GridSet)
+                .after(trace_viewer_ui)
+                .run_if(in_state(AppState::Simulate).or_else(in_state(AppState::SimulateFinish))),
+        );
+
+        app.add_system(
+            chaos_menu_ui
+                .in_set(GridSet)
+                .after(metrics_dashboard_ui)
+                .before(apply_faults)
+                .run_if(in_state(AppState::Simulate).and_then(in_state(GameMode::Sandbox))),
+        );
+
+        app.add_system(
+            level_editor_ui
+                .in_set(GridSet)
+                .after(console_log_ui)
+                .run_if(in_state(AppState::LevelEditor)),
+        );
 
         app.add_system(
             (|| true)
@@ -64,10 +117,41 @@ impl Plugin for GameUiPlugin {
     }
 }
 
+fn level_load_errors_ui(
+    mut contexts: EguiContexts,
+    mut app_state: ResMut<NextState<AppState>>,
+    level_load_errors: Res<LevelLoadErrors>,
+) {
+    // Loading continues straight to the main menu when there are no problems.
+    if level_load_errors.0.is_empty() {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.heading("Some levels failed to load");
+        ui.label("These levels have been left out of the game.");
+
+        if ui.button("Continue").clicked() {
+            app_state.set(AppState::MainMenu);
+        }
+
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for diagnostic in level_load_errors.0.iter() {
+                ui.colored_label(ERROR_COLOR, diagnostic.to_string());
+            }
+        });
+    });
+}
+
 fn main_menu_ui(
     mut contexts: EguiContexts,
     mut app_state: ResMut<NextState<AppState>>,
     mut game_mode: ResMut<NextState<GameMode>>,
+    mut level_state: ResMut<LevelState>,
 ) {
     let ctx = contexts.ctx_mut();
 
@@ -77,12 +161,21 @@ fn main_menu_ui(
 
             if ui.button("Level Select").clicked() {
                 app_state.set(AppState::LevelSelect);
+                game_mode.set(GameMode::Levels);
             }
 
             if ui.button("Sandbox Mode").clicked() {
                 app_state.set(AppState::Edit);
                 game_mode.set(GameMode::Sandbox);
             }
+
+            if ui.button("Level Editor").clicked() {
+                // Levels are authored on an empty grid.
+                level_state.current_level = None;
+
+                app_state.set(AppState::LevelEditor);
+                game_mode.set(GameMode::Sandbox);
+            }
         });
     });
 }
@@ -93,6 +186,7 @@ fn level_select_ui(
     handles: Res<Handles>,
     levels: Res<Assets<Level>>,
     mut level_state: ResMut<LevelState>,
+    mut progress: ResMut<Progress>,
 ) {
     let ctx = contexts.ctx_mut();
 
@@ -100,16 +194,78 @@ fn level_select_ui(
         ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
             ui.heading("Levels");
 
-            for (idx, level_handle) in handles.levels.iter().enumerate() {
-                let level = levels.get(level_handle).unwrap();
-                let level_button_label = format!("Level {}: {}", idx + 1, level.name);
+            let level_name = |id: &String| {
+                handles
+                    .levels
+                    .iter()
+                    .find(|level| &level.id == id)
+                    .and_then(|level| levels.get(&level.handle))
+                    .map_or(id.clone(), |level| level.name.clone())
+            };
+
+            egui::Grid::new("level_select").show(ui, |ui| {
+                for (idx, level_info) in handles.levels.iter().enumerate() {
+                    if idx == 0 || handles.levels[idx - 1].chapter != level_info.chapter {
+                        ui.heading(&level_info.chapter);
+                        ui.end_row();
+                    }
 
-                if ui.button(level_button_label).clicked() {
-                    level_state.current_level = Some(idx);
+                    let level = levels.get(&level_info.handle).unwrap();
+                    let level_button_label = format!("Level {}: {}", idx + 1, level.name);
+                    let is_unlocked = progress.is_unlocked(level);
 
-                    app_state.set(AppState::Edit);
+                    if ui
+                        .add_enabled(is_unlocked, egui::Button::new(level_button_label))
+                        .clicked()
+                    {
+                        level_state.current_level = Some(idx);
+
+                        app_state.set(AppState::Edit);
+                    }
+
+                    let level_progress =
+                        progress.level(&level_info.id).cloned().unwrap_or_default();
+
+                    if !is_unlocked {
+                        let prerequisites: Vec<_> =
+                            level.prerequisites.iter().map(level_name).collect();
+                        ui.label(format!("🔒 Complete {}", prerequisites.join(", ")));
+                    } else if level_progress.completed {
+                        let mut badge = "✔ Completed".to_string();
+
+                        if let Some(stars) = level_progress.best_stars {
+                            badge.push_str(&format!(
+                                " {}{}",
+                                "★".repeat(stars.into()),
+                                "☆".repeat(3u8.saturating_sub(stars).into())
+                            ));
+                        }
+
+                        if let Some(best_score) = level_progress.best_score {
+                            badge.push_str(&format!(" Best: {best_score} pts"));
+                        }
+
+                        ui.colored_label(Color32::GREEN, badge);
+                    } else {
+                        ui.label("");
+                    }
+
+                    if is_unlocked
+                        && level_progress.design.is_some()
+                        && ui
+                            .button("Start over")
+                            .on_hover_text("Discard the saved design of this level")
+                            .clicked()
+                    {
+                        progress.clear_design(&level_info.id);
+                        level_state.current_level = Some(idx);
+
+                        app_state.set(AppState::Edit);
+                    }
+
+                    ui.end_row();
                 }
-            }
+            });
         });
     });
 }
@@ -119,6 +275,8 @@ fn tools_ui(
     mut add_component_events: EventWriter<AddComponentEvent>,
     curr_app_state: Res<State<AppState>>,
     mut app_state: ResMut<NextState<AppState>>,
+    system_cost: SystemCost,
+    system_constraints: SystemConstraints,
 ) {
     let ctx = contexts.ctx_mut();
 
@@ -126,21 +284,36 @@ fn tools_ui(
         .default_width(200.0)
         .show(ctx, |ui| {
             egui::ScrollArea::vertical().show(ui, |ui| {
-                ui.add_enabled_ui(curr_app_state.0 == AppState::Edit, |ui| {
+                let is_editing =
+                    curr_app_state.0 == AppState::Edit || curr_app_state.0 == AppState::LevelEditor;
+
+                ui.add_enabled_ui(is_editing, |ui| {
                     ui.heading("Components");
 
-                    if ui.button("Add Client").clicked() {
+                    let mut add_button = |node_type: NodeType| {
+                        ui.add_enabled(
+                            system_constraints.can_add(&node_type),
+                            egui::Button::new(format!("Add {node_type}")),
+                        )
+                        .clicked()
+                    };
+
+                    if add_button(NodeType::Client) {
                         add_component_events.send(AddComponentEvent::new_client());
                     }
 
-                    if ui.button("Add Server").clicked() {
+                    if add_button(NodeType::Server) {
                         add_component_events.send(AddComponentEvent::new_server());
                     }
 
-                    if ui.button("Add Database").clicked() {
+                    if add_button(NodeType::Database) {
                         add_component_events.send(AddComponentEvent::new_database());
                     }
 
+                    if curr_app_state.0 == AppState::LevelEditor {
+                        return;
+                    }
+
                     ui.heading("Simulation");
 
                     if ui.button("Execute").clicked() {
@@ -149,6 +322,48 @@ fn tools_ui(
                 });
             });
 
+            let constraint_results = system_constraints.results();
+
+            if !constraint_results.is_empty() {
+                ui.heading("Constraints");
+                expectations_results_ui(ui, &constraint_results);
+            }
+
+            if system_cost.has_pricing() {
+                ui.heading("Cost");
+
+                egui::Grid::new("tools_cost").show(ui, |ui| {
+                    ui.label("Infrastructure:");
+                    ui.label(system_cost.infrastructure_cost().to_string());
+                    ui.end_row();
+
+                    if curr_app_state.0 != AppState::Edit {
+                        ui.label("Requests:");
+                        ui.label(system_cost.request_cost().to_string());
+                        ui.end_row();
+                    }
+
+                    ui.label("Total:");
+                    let total = system_cost.total_cost().to_string();
+                    if system_cost.is_within_budget() {
+                        ui.label(total);
+                    } else {
+                        ui.colored_label(ERROR_COLOR, total);
+                    }
+                    ui.end_row();
+
+                    if let Some(budget) = system_cost.budget() {
+                        ui.label("Budget:");
+                        ui.label(budget.to_string());
+                        ui.end_row();
+                    }
+                });
+
+                if !system_cost.is_within_budget() {
+                    ui.colored_label(ERROR_COLOR, "Over budget.");
+                }
+            }
+
             if curr_app_state.0 == AppState::Simulate && ui.button("Stop").clicked() {
                 app_state.set(AppState::Edit);
             }
@@ -178,6 +393,550 @@ fn bottom_panel_ui(mut contexts: EguiContexts, current_level: CurrentLevel) {
     }
 }
 
+fn console_log_ui(
+    mut contexts: EguiContexts,
+    console_log: Res<ConsoleLog>,
+    nodes: Query<(Entity, &NodeName, &NodeType)>,
+    mut node_filter: Local<Option<Entity>>,
+) {
+    let ctx = contexts.ctx_mut();
+
+    let node_label = |entity: Entity| node_label(&nodes, entity);
+
+    egui::TopBottomPanel::bottom("console_log_panel")
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("Console");
+
+                egui::ComboBox::from_id_source("console_log_node_filter")
+                    .selected_text(
+                        node_filter
+                            .map(node_label)
+                            .unwrap_or_else(|| "All nodes".to_string()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut *node_filter, None, "All nodes");
+
+                        for (entity, _, _) in nodes.iter() {
+                            ui.selectable_value(
+                                &mut *node_filter,
+                                Some(entity),
+                                node_label(entity),
+                            );
+                        }
+                    });
+            });
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for entry in console_log
+                        .entries()
+                        .iter()
+                        .filter(|entry| node_filter.is_none_or(|node| node == entry.node))
+                    {
+                        let color = match entry.level {
+                            LogLevel::Log => ui.visuals().text_color(),
+                            LogLevel::Warn => Color32::YELLOW,
+                            LogLevel::Error => ERROR_COLOR,
+                        };
+
+                        let text = format!(
+                            "[{:.2}s] [{}] [{}] {}",
+                            entry.time.as_secs_f32(),
+                            node_label(entry.node),
+                            &entry.trace_id.to_string()[..8],
+                            entry.message
+                        );
+
+                        ui.label(egui::RichText::new(text).monospace().color(color));
+                    }
+                    ui.allocate_space(ui.available_size());
+                });
+        });
+}
+
+fn node_label(nodes: &Query<(Entity, &NodeName, &NodeType)>, entity: Entity) -> String {
+    nodes
+        .get(entity)
+        .map(|(_, node_name, node_type)| node_name.label(node_type, entity))
+        .unwrap_or_default()
+}
+
+fn trace_viewer_ui(
+    mut contexts: EguiContexts,
+    trace_store: Res<TraceStore>,
+    simulation_clock: Res<SimulationClock>,
+    nodes: Query<(Entity, &NodeName, &NodeType)>,
+    export_node_query: Query<(Entity, &NodeName, &NodeType, Option<&Hostname>)>,
+    mut selected_trace: Local<Option<Uuid>>,
+    mut export_result: Local<Option<String>>,
+) {
+    let ctx = contexts.ctx_mut();
+    let now = simulation_clock.elapsed();
+
+    let root_label = |span: &SpanRecord| {
+        format!(
+            "[{}] {} {}",
+            &span.context.trace_id.to_string()[..8],
+            node_label(&nodes, span.caller),
+            span.operation
+        )
+    };
+
+    egui::Window::new("Traces")
+        .default_open(false)
+        .resizable(true)
+        .show(ctx, |ui| {
+            if selected_trace.is_none() {
+                *selected_trace = trace_store.roots().next().map(|span| span.context.trace_id);
+            }
+
+            egui::ComboBox::from_id_source("trace_viewer_trace")
+                .width(300.0)
+                .selected_text(
+                    trace_store
+                        .roots()
+                        .find(|span| Some(span.context.trace_id) == *selected_trace)
+                        .map(root_label)
+                        .unwrap_or_else(|| "No traces".to_string()),
+                )
+                .show_ui(ui, |ui| {
+                    for span in trace_store.roots() {
+                        ui.selectable_value(
+                            &mut *selected_trace,
+                            Some(span.context.trace_id),
+                            root_label(span),
+                        );
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                for format in TraceFormat::iter() {
+                    if ui.button(format!("Export {format}")).clicked() {
+                        let traces = export_traces(
+                            trace_store.spans(),
+                            &export_nodes(&export_node_query),
+                            format,
+                        );
+                        let path = Path::new(format.file_name());
+
+                        *export_result = Some(match write_traces(path, &traces) {
+                            Ok(()) => format!("Exported to {}", path.display()),
+                            Err(error) => format!("Export failed: {error}"),
+                        });
+                    }
+                }
+
+                if let Some(export_result) = &*export_result {
+                    ui.label(export_result);
+                }
+            });
+
+            ui.separator();
+
+            let Some(trace_id) = *selected_trace else {
+                return;
+            };
+
+            let waterfall = trace_store.waterfall(trace_id);
+
+            let Some(trace_start) = waterfall.iter().map(|(_, span)| span.start).min() else {
+                return;
+            };
+            let trace_end = waterfall
+                .iter()
+                .map(|(_, span)| span.end.unwrap_or(now))
+                .max()
+                .unwrap_or(trace_start);
+            let trace_duration = (trace_end - trace_start).as_secs_f32().max(f32::EPSILON);
+
+            egui::Grid::new("trace_viewer_waterfall")
+                .striped(true)
+                .show(ui, |ui| {
+                    for (depth, span) in waterfall {
+                        ui.label(format!(
+                            "{}{} → {}: {}",
+                            "  ".repeat(depth),
+                            node_label(&nodes, span.caller),
+                            node_label(&nodes, span.callee),
+                            span.operation
+                        ));
+
+                        let (rect, response) = ui.allocate_exact_size(
+                            egui::vec2(WATERFALL_WIDTH, ui.spacing().interact_size.y),
+                            egui::Sense::hover(),
+                        );
+
+                        let offset = (span.start - trace_start).as_secs_f32() / trace_duration;
+                        let width = span.duration(now).as_secs_f32() / trace_duration;
+
+                        let bar = egui::Rect::from_min_size(
+                            rect.min + egui::vec2(offset * rect.width(), 2.0),
+                            egui::vec2((width * rect.width()).max(2.0), rect.height() - 4.0),
+                        );
+
+                        let color = match span.end {
+                            None => Color32::GRAY,
+                            Some(_) if span.is_error() => ERROR_COLOR,
+                            Some(_) => Color32::GREEN,
+                        };
+
+                        ui.painter().rect_filled(bar, 2.0, color);
+
+                        response.on_hover_text(format!(
+                            "Start: {:.2}s\nEnd: {}",
+                            span.start.as_secs_f32(),
+                            span.end
+                                .map(|end| format!("{:.2}s", end.as_secs_f32()))
+                                .unwrap_or_else(|| "In flight".to_string())
+                        ));
+
+                        ui.label(format!("{:.2}s", span.duration(now).as_secs_f32()));
+                        ui.label(
+                            span.status
+                                .map(|status| status.to_string())
+                                .unwrap_or_default(),
+                        );
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+const WATERFALL_WIDTH: f32 = 300.0;
+
+fn metrics_dashboard_ui(
+    mut contexts: EguiContexts,
+    trace_store: Res<TraceStore>,
+    metrics: Res<Metrics>,
+    nodes: Query<(Entity, &NodeName, &NodeType)>,
+    servers: Query<&Server>,
+    simulation_clock: Res<SimulationClock>,
+) {
+    let ctx = contexts.ctx_mut();
+    let elapsed = simulation_clock.elapsed();
+
+    // Requests to a server are grouped by the endpoint that handled them.
+    let endpoint_label = |span: &SpanRecord| match &span.operation {
+        SpanOperation::Http { method, path, .. } => Some(
+            span.endpoint
+                .as_ref()
+                .map(|(method, path)| format!("{} {path}", format_method(method)))
+                .unwrap_or_else(|| format!("{} {path} (unmatched)", format_method(method))),
+        ),
+        SpanOperation::Database { .. } => None,
+    };
+
+    egui::Window::new("Metrics")
+        .default_open(false)
+        .resizable(true)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("metrics_request_stats")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for header in [
+                            "Node", "Requests", "2xx", "3xx", "4xx", "5xx", "Errors", "p50", "p95",
+                            "p99", "Req/s",
+                        ] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+
+                        for (entity, _, _) in nodes.iter() {
+                            let spans = || {
+                                trace_store
+                                    .spans()
+                                    .iter()
+                                    .filter(move |span| span.callee == entity)
+                            };
+
+                            let stats = RequestStats::from_spans(spans());
+
+                            if stats.count == 0 {
+                                continue;
+                            }
+
+                            request_stats_row(ui, node_label(&nodes, entity), &stats, elapsed);
+
+                            if !servers.contains(entity) {
+                                continue;
+                            }
+
+                            let mut endpoints: Vec<String> = vec![];
+                            for label in spans().filter_map(endpoint_label) {
+                                if !endpoints.contains(&label) {
+                                    endpoints.push(label);
+                                }
+                            }
+
+                            for label in endpoints {
+                                let stats =
+                                    RequestStats::from_spans(spans().filter(|span| {
+                                        endpoint_label(span).as_ref() == Some(&label)
+                                    }));
+
+                                request_stats_row(ui, format!("    {label}"), &stats, elapsed);
+                            }
+                        }
+                    });
+
+                for (entity, _, node_type) in nodes.iter() {
+                    if *node_type != NodeType::Server {
+                        continue;
+                    }
+
+                    let samples: Vec<_> = metrics.server_samples(entity).collect();
+
+                    if samples.is_empty() {
+                        continue;
+                    }
+
+                    ui.separator();
+                    ui.label(node_label(&nodes, entity));
+
+                    let points = |value: fn(&ServerSample) -> usize| -> PlotPoints {
+                        samples
+                            .iter()
+                            .map(|sample| [sample.time.as_secs_f64(), value(sample) as f64])
+                            .collect()
+                    };
+
+                    Plot::new(("metrics_server_load", entity))
+                        .height(80.0)
+                        .include_y(0.0)
+                        .allow_drag(false)
+                        .allow_zoom(false)
+                        .allow_scroll(false)
+                        .legend(Legend::default())
+                        .show(ui, |plot_ui| {
+                            plot_ui.line(
+                                Line::new(points(|sample| sample.concurrency)).name("Concurrency"),
+                            );
+                            plot_ui.line(
+                                Line::new(points(|sample| sample.queue_depth)).name("Queue depth"),
+                            );
+                        });
+                }
+            });
+        });
+}
+
+fn chaos_menu_ui(
+    mut contexts: EguiContexts,
+    mut chaos_menu: ResMut<ChaosMenu>,
+    mut faults: ResMut<Faults>,
+    nodes: Query<(Entity, &NodeName, &NodeType)>,
+) {
+    let Some(target) = chaos_menu.target else {
+        return;
+    };
+
+    let ctx = contexts.ctx_mut();
+    let pointer_pos = ctx.input(|input| input.pointer.interact_pos());
+    let mut open = true;
+
+    let mut window = egui::Window::new("Chaos")
+        .id(egui::Id::new(target))
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false);
+
+    if let Some(pointer_pos) = pointer_pos {
+        window = window.default_pos(pointer_pos);
+    }
+
+    window.show(ctx, |ui| match target {
+        ChaosTarget::Node(entity) => {
+            let Ok((_, _, node_type)) = nodes.get(entity) else {
+                return;
+            };
+
+            ui.heading(node_label(&nodes, entity));
+
+            if matches!(node_type, NodeType::Server | NodeType::Database) {
+                if faults.is_down(entity) {
+                    if ui.button("Restart").clicked() {
+                        faults.set_down(entity, false);
+                    }
+                } else if ui.button("Kill").clicked() {
+                    faults.set_down(entity, true);
+                }
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Latency");
+
+                let mut latency_ms = faults.latency(entity).as_millis() as u64;
+
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut latency_ms)
+                            .clamp_range(0..=10_000)
+                            .suffix(" ms"),
+                    )
+                    .changed()
+                {
+                    faults.set_latency(entity, Duration::from_millis(latency_ms));
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut chaos_menu.drop_count).clamp_range(1..=100));
+
+                if ui.button("Drop next messages").clicked() {
+                    faults.drop_next(entity, chaos_menu.drop_count);
+                }
+            });
+
+            let drops_remaining = faults.drops_remaining(entity);
+
+            if drops_remaining > 0 {
+                ui.label(format!("{drops_remaining} messages left to drop"));
+            }
+        }
+        ChaosTarget::Connection(a, b) => {
+            ui.heading(format!(
+                "{} ↔ {}",
+                node_label(&nodes, a),
+                node_label(&nodes, b)
+            ));
+
+            let mut drop_rate = faults.manual_drop_rate(a, b);
+
+            if drop_rate >= 1.0 {
+                if ui.button("Repair").clicked() {
+                    faults.set_drop_rate(a, b, 0.0);
+                }
+            } else if ui.button("Cut").clicked() {
+                faults.set_drop_rate(a, b, 1.0);
+            }
+
+            if ui
+                .add(egui::Slider::new(&mut drop_rate, 0.0..=1.0).text("Flakiness"))
+                .changed()
+            {
+                faults.set_drop_rate(a, b, drop_rate);
+            }
+        }
+    });
+
+    if !open {
+        chaos_menu.target = None;
+    }
+}
+
+fn request_stats_row(ui: &mut egui::Ui, label: String, stats: &RequestStats, elapsed: Duration) {
+    let format_latency = |percentile: f32| {
+        stats
+            .latency_percentile(percentile)
+            .map(|latency| format!("{:.2}s", latency.as_secs_f32()))
+            .unwrap_or_default()
+    };
+
+    ui.label(label);
+    ui.label(stats.count.to_string());
+    for count in &stats.status_classes[1..] {
+        ui.label(count.to_string());
+    }
+    ui.label(format!("{:.0}%", stats.error_rate() * 100.0));
+    ui.label(format_latency(50.0));
+    ui.label(format_latency(95.0));
+    ui.label(format_latency(99.0));
+    ui.label(format!("{:.2}", stats.throughput(elapsed)));
+    ui.end_row();
+}
+
+#[allow(clippy::type_complexity)]
+fn level_editor_ui(
+    mut contexts: EguiContexts,
+    mut level_editor_state: ResMut<LevelEditorState>,
+    mut app_state: ResMut<NextState<AppState>>,
+    nodes: Query<(
+        &NodeType,
+        &NodeName,
+        &Transform,
+        Option<&Hostname>,
+        Option<&Client>,
+        Option<&Server>,
+        Option<&Database>,
+        &NodeConnections,
+    )>,
+) {
+    let ctx = contexts.ctx_mut();
+
+    egui::Window::new("Level").show(ctx, |ui| {
+        egui::Grid::new("level_editor")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut level_editor_state.name);
+                ui.end_row();
+
+                ui.label("File:");
+                ui.text_edit_singleline(&mut level_editor_state.path);
+                ui.end_row();
+            });
+
+        ui.label("Description:");
+        ui.add(
+            egui::TextEdit::multiline(&mut level_editor_state.description)
+                .desired_width(f32::INFINITY),
+        );
+
+        ui.horizontal(|ui| {
+            if ui.button("Export").clicked() {
+                let nodes: Vec<_> = nodes
+                    .iter()
+                    .map(
+                        |(
+                            node_type,
+                            node_name,
+                            transform,
+                            hostname,
+                            client,
+                            server,
+                            database,
+                            connections,
+                        )| EditorNode {
+                            node_type: *node_type,
+                            name: &node_name.0,
+                            position: transform.translation.truncate(),
+                            hostname,
+                            client,
+                            server,
+                            database,
+                            connections: connections
+                                .iter()
+                                .filter_map(|(other, _)| nodes.get(*other).ok())
+                                .map(|(_, other_name, ..)| other_name.0.as_str())
+                                .collect(),
+                        },
+                    )
+                    .collect();
+
+                level_editor_state.export(&nodes);
+            }
+
+            if ui.button("Main Menu").clicked() {
+                app_state.set(AppState::MainMenu);
+            }
+        });
+
+        match &level_editor_state.export_result {
+            Some(Ok(message)) => {
+                ui.colored_label(Color32::GREEN, message);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(ERROR_COLOR, error);
+            }
+            None => {}
+        }
+    });
+}
+
 fn level_finish_modal_ui(
     In(should_open): In<bool>,
     mut contexts: EguiContexts,
@@ -202,6 +961,34 @@ fn level_finish_modal_ui(
                     }
                 ),
             );
+
+            if let Some(score) = &level_state.score {
+                ui.separator();
+
+                egui::Grid::new("level_finish_score")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (name, amount, points) in score.components() {
+                            ui.label(name);
+                            ui.label(amount);
+                            ui.label(format!("{points:.0} pts"));
+                            ui.end_row();
+                        }
+
+                        ui.strong("Score");
+                        ui.label("Lower is better");
+                        ui.strong(format!("{} pts", score.score()));
+                        ui.end_row();
+                    });
+
+                if let Some(stars) = score.stars {
+                    ui.heading(format!(
+                        "{}{}",
+                        "★".repeat(stars.into()),
+                        "☆".repeat(3u8.saturating_sub(stars).into())
+                    ));
+                }
+            }
         });
         modal.buttons(ui, |ui| {
             modal.button(ui, "Close");
@@ -216,18 +1003,21 @@ fn level_finish_modal_ui(
     }
 }
 
-fn node_inspector_ui<T: View + Component + SystemNodeTrait>(
+fn node_inspector_ui<T: View + LevelEditorView + Component + SystemNodeTrait>(
     mut contexts: EguiContexts,
     mut nodes: Query<(&PickSelection, Entity, &mut NodeName, &mut NodeType, &mut T)>,
     app_state: Res<State<AppState>>,
     delete_node_event: EventWriter<DeleteNodeEvent>,
     hostnames: Query<(Entity, &mut Hostname)>,
+    locked: Query<(), With<Locked>>,
 ) {
     if let Some((_, entity, mut node_name, mut node_type, mut node)) =
         nodes.iter_mut().find(|query| query.0.is_selected)
     {
         let ctx = contexts.ctx_mut();
         let can_be_edited = node.can_be_edited();
+        let is_level_editor = app_state.0 == AppState::LevelEditor;
+        let enabled = can_be_edited && (app_state.0 == AppState::Edit || is_level_editor);
 
         show_inspector(
             ctx,
@@ -235,7 +1025,9 @@ fn node_inspector_ui<T: View + Component + SystemNodeTrait>(
             &mut node_type,
             hostnames,
             node.as_mut(),
-            can_be_edited && app_state.0 == AppState::Edit,
+            enabled,
+            enabled && !locked.contains(entity),
+            is_level_editor,
             delete_node_event,
             entity,
         );
@@ -243,13 +1035,15 @@ fn node_inspector_ui<T: View + Component + SystemNodeTrait>(
 }
 
 #[allow(clippy::complexity)]
-fn show_inspector<T: View>(
+fn show_inspector<T: View + LevelEditorView>(
     ctx: &mut Context,
     node_name: &mut NodeName,
     node_type: &mut NodeType,
     mut hostnames: Query<(Entity, &mut Hostname)>,
     node: &mut T,
     enabled: bool,
+    can_be_deleted: bool,
+    is_level_editor: bool,
     mut delete_node_event: EventWriter<DeleteNodeEvent>,
     entity: Entity,
 ) {
@@ -276,7 +1070,11 @@ fn show_inspector<T: View>(
 
                 node.ui(ui, enabled);
 
-                if enabled && ui.button("Delete node").clicked() {
+                if is_level_editor {
+                    node.level_editor_ui(ui, egui::Id::new(entity));
+                }
+
+                if can_be_deleted && ui.button("Delete node").clicked() {
                     delete_node_event.send(DeleteNodeEvent(entity));
                 }
 
@@ -303,6 +1101,53 @@ trait View {
     fn ui(&mut self, ui: &mut egui::Ui, editable: bool);
 }
 
+// What only a level author can edit about a node.
+trait LevelEditorView {
+    fn level_editor_ui(&mut self, _ui: &mut egui::Ui, _id: egui::Id) {}
+}
+
+// Edits a value as JSON. The text is kept while it is invalid, so that it can be fixed, and the
+// value is only updated once the text parses and passes `is_valid`.
+fn json_editor<T: Serialize + DeserializeOwned>(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    value: &mut T,
+    is_valid: impl Fn(&T) -> bool,
+) {
+    let mut text = ui
+        .data_mut(|data| data.get_temp::<String>(id))
+        .unwrap_or_else(|| serde_json::to_string_pretty(value).unwrap());
+
+    let parse = |text: &str| serde_json::from_str::<T>(text).ok().filter(&is_valid);
+
+    let stroke = if parse(&text).is_some() {
+        egui::Stroke::NONE
+    } else {
+        ERROR_FRAME_STROKE
+    };
+
+    let response = create_error_frame(stroke)
+        .show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut text)
+                    .font(egui::TextStyle::Monospace)
+                    .code_editor()
+                    .desired_rows(1)
+                    .lock_focus(true)
+                    .desired_width(f32::INFINITY),
+            )
+        })
+        .inner;
+
+    if response.changed() {
+        if let Some(parsed) = parse(&text) {
+            *value = parsed;
+        }
+    }
+
+    ui.data_mut(|data| data.insert_temp(id, text));
+}
+
 impl View for NodeName {
     fn ui(&mut self, ui: &mut egui::Ui, editable: bool) {
         ui.horizontal(|ui| {
@@ -432,6 +1277,31 @@ impl View for Client {
                 }
             });
 
+            if editable || !config.headers.is_empty() {
+                ui.label("Headers:");
+
+                let mut header_idx_to_delete = None;
+
+                for (header_idx, (name, value)) in config.headers.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_label_toggle(editable, name);
+                        ui.text_edit_label_toggle(editable, value);
+
+                        if editable && ui.button("Delete").clicked() {
+                            header_idx_to_delete = Some(header_idx);
+                        }
+                    });
+                }
+
+                if let Some(header_idx) = header_idx_to_delete {
+                    config.headers.remove(header_idx);
+                }
+
+                if editable && ui.button("Add header").clicked() {
+                    config.headers.push(Default::default());
+                }
+            }
+
             if config.method == HttpMethod::Post || config.method == HttpMethod::Put {
                 let (stroke, override_text_color) = if config.is_body_valid() {
                     (egui::Stroke::NONE, None)
@@ -473,24 +1343,7 @@ impl View for Client {
                 );
             }
 
-            for (passed, message) in config.expectations_results.iter() {
-                let mut job = LayoutJob::default();
-                let (icon, color) = if *passed {
-                    ("✔", Color32::GREEN)
-                } else {
-                    ("✖", Color32::RED)
-                };
-                job.append(
-                    icon,
-                    0.0,
-                    TextFormat {
-                        color,
-                        ..Default::default()
-                    },
-                );
-                job.append(message, 0.0, TextFormat::default());
-                ui.label(job);
-            }
+            expectations_results_ui(ui, &config.expectations_results);
 
             ui.separator();
         }
@@ -499,33 +1352,194 @@ impl View for Client {
             self.request_configs.remove(i);
         }
 
+        // Only whether hidden requests passed is shown, so they cannot be solved by hard-coding
+        // their responses.
+        if !self.hidden_request_configs.is_empty() {
+            ui.heading("Hidden Requests");
+            ui.label(format!(
+                "{} more requests are sent after these.",
+                self.hidden_request_configs.len()
+            ));
+
+            let results: Vec<_> = self
+                .hidden_request_configs
+                .iter()
+                .enumerate()
+                .filter(|(_, config)| !config.expectations_results.is_empty())
+                .map(|(idx, config)| {
+                    let passed = config
+                        .expectations_results
+                        .iter()
+                        .all(|(passed, _)| *passed);
+
+                    (
+                        passed,
+                        format!(
+                            "Hidden request {} {}",
+                            idx + 1,
+                            if passed { "passed" } else { "failed" }
+                        ),
+                    )
+                })
+                .collect();
+
+            expectations_results_ui(ui, &results);
+
+            ui.separator();
+        }
+
+        if !self.get_expectations().is_empty() {
+            ui.heading("Expectations");
+
+            if self.expectations_results.is_empty() {
+                for expectation in self.get_expectations() {
+                    ui.label(expectation.to_string());
+                }
+            } else {
+                expectations_results_ui(ui, &self.expectations_results);
+            }
+
+            ui.separator();
+        }
+
         if editable && ui.button("Add Request").clicked() {
             self.request_configs.push(RequestConfig::default());
         }
     }
 }
 
+impl LevelEditorView for Client {
+    fn level_editor_ui(&mut self, ui: &mut egui::Ui, id: egui::Id) {
+        ui.separator();
+        ui.heading("Expectations");
+        ui.label("Responses must meet every expectation, e.g. {\"Status\": 200}.");
+
+        for (idx, config) in self.request_configs.iter_mut().enumerate() {
+            ui.label(format!(
+                "{} {}{}",
+                format_method(&config.method),
+                config.url,
+                config.path
+            ));
+
+            json_editor(ui, id.with(idx), config.expectations_mut(), |_| true);
+        }
+
+        ui.label("Every response of the Client, together, must meet every expectation, e.g. \"Throttled\".");
+
+        json_editor(ui, id.with("client"), self.expectations_mut(), |_| true);
+    }
+}
+
+impl LevelEditorView for Server {}
+
+impl LevelEditorView for Database {
+    fn level_editor_ui(&mut self, ui: &mut egui::Ui, id: egui::Id) {
+        ui.separator();
+        ui.heading("Initial Documents");
+        ui.label("Every document needs an integer id.");
+
+        let mut initial_documents = self.get_initial_documents();
+
+        json_editor(
+            ui,
+            id,
+            &mut initial_documents,
+            |documents: &Vec<Document>| {
+                documents
+                    .iter()
+                    .all(|document| document.get("id").is_some_and(Value::is_i64))
+            },
+        );
+
+        if initial_documents != self.get_initial_documents() {
+            self.set_initial_documents(initial_documents);
+        }
+
+        ui.heading("Expectations");
+        ui.label("Once every Client finishes, the documents must meet every expectation, e.g. {\"Count\": 1}.");
+
+        json_editor(ui, id.with("expectations"), self.expectations_mut(), |_| {
+            true
+        });
+    }
+}
+
 impl View for Server {
     fn ui(&mut self, ui: &mut egui::Ui, editable: bool) {
         ui.separator();
 
+        if self.state == ServerState::Down {
+            ui.colored_label(ERROR_COLOR, "Down");
+        }
+
         if let Some(max_concurrent_connections) = self.max_concurrent_connections() {
             ui.label("Max Concurrent Connections:");
             ui.label(max_concurrent_connections.to_string());
         }
 
+        if let Some(cpu_cost) = self.cpu_cost() {
+            ui.label("CPU Cost:");
+            ui.label(format!("{cpu_cost}ms per 1,000 statements"));
+        }
+
+        if !self.base_cost().is_zero() {
+            ui.label("Base Cost:");
+            ui.label(format!("{}ms", self.base_cost().as_millis()));
+        }
+
+        if !self.step_cost().is_zero() {
+            ui.label("Step Cost:");
+            ui.label(format!("{}ms", self.step_cost().as_millis()));
+        }
+
+        if let Some(workers) = self.workers() {
+            ui.label("Busy Workers:");
+            ui.label(format!("{}/{}", self.num_busy_workers(), workers));
+
+            ui.label("Backlog:");
+            match self.max_backlog() {
+                Some(max_backlog) => {
+                    ui.label(format!("{}/{}", self.num_queued_steps(), max_backlog))
+                }
+                None => ui.label(self.num_queued_steps().to_string()),
+            };
+        }
+
+        egui::CollapsingHeader::new("State")
+            .id_source("server_state")
+            .show(ui, |ui| {
+                let mut pretty_string = serde_json::to_string_pretty(&self.local_state).unwrap();
+                ui.add(
+                    egui::TextEdit::multiline(&mut pretty_string)
+                        .interactive(false)
+                        .font(egui::TextStyle::Monospace)
+                        .code_editor()
+                        .desired_rows(1)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+
+        ui.separator();
+
         ui.heading("Endpoints");
         ui.separator();
 
         let mut endpoint_idx_to_delete = None;
 
-        let endpoint_path_valid: Vec<_> = (0..self.endpoint_handlers.len())
-            .map(|e| self.is_path_valid(e))
+        let endpoint_path_results: Vec<_> = (0..self.endpoint_handlers.len())
+            .map(|e| self.validate_path(e))
             .collect();
 
         for (idx, endpoint) in self.endpoint_handlers.iter_mut().enumerate() {
+            let editable = editable && !endpoint.locked;
+
+            if endpoint.locked {
+                ui.label("Locked by the level");
+            }
+
             ui.horizontal(|ui| {
-                let (stroke, override_text_color) = if endpoint_path_valid[idx] {
+                let (stroke, override_text_color) = if endpoint_path_results[idx].is_ok() {
                     (egui::Stroke::NONE, None)
                 } else {
                     (ERROR_FRAME_STROKE, Some(ERROR_COLOR))
@@ -540,6 +1554,10 @@ impl View for Server {
                 });
             });
 
+            if let Err(path_error) = &endpoint_path_results[idx] {
+                ui.colored_label(ERROR_COLOR, path_error.to_string());
+            }
+
             if editable {
                 egui::ComboBox::from_id_source(idx)
                     .selected_text(format_method(&endpoint.method))
@@ -595,11 +1613,268 @@ impl View for Server {
         if editable && ui.button("Add endpoint").clicked() {
             self.endpoint_handlers.push(Endpoint::default());
         }
+
+        ui.separator();
+        ui.heading("Middleware");
+        ui.separator();
+
+        let mut middleware_idx_to_delete = None;
+        let mut middleware_idx_to_move_up = None;
+        let num_middlewares = self.middlewares.len();
+
+        for (idx, middleware) in self.middlewares.iter_mut().enumerate() {
+            let mut header_text = egui::RichText::new(format!("Middleware {}", idx + 1));
+
+            let stroke = if middleware.is_handler_valid() {
+                egui::Stroke::NONE
+            } else {
+                header_text = header_text.color(ERROR_COLOR);
+                ERROR_FRAME_STROKE
+            };
+
+            egui::CollapsingHeader::new(header_text)
+                .id_source(("middleware", idx))
+                .show(ui, |ui| {
+                    create_error_frame(stroke).show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut middleware.handler)
+                                .interactive(editable)
+                                .font(egui::TextStyle::Monospace) // for cursor height
+                                .code_editor()
+                                .desired_rows(1)
+                                .lock_focus(true)
+                                .desired_width(f32::INFINITY),
+                        );
+                    });
+                });
+
+            if editable {
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(idx > 0, egui::Button::new("Up")).clicked() {
+                        middleware_idx_to_move_up = Some(idx);
+                    }
+
+                    if ui
+                        .add_enabled(idx + 1 < num_middlewares, egui::Button::new("Down"))
+                        .clicked()
+                    {
+                        middleware_idx_to_move_up = Some(idx + 1);
+                    }
+
+                    if ui.button("Delete middleware").clicked() {
+                        middleware_idx_to_delete = Some(idx);
+                    }
+                });
+            }
+
+            ui.separator();
+        }
+
+        if let Some(idx) = middleware_idx_to_move_up {
+            self.middlewares.swap(idx - 1, idx);
+        }
+
+        if let Some(idx) = middleware_idx_to_delete {
+            self.middlewares.remove(idx);
+        }
+
+        if editable && ui.button("Add middleware").clicked() {
+            self.middlewares.push(Middleware::default());
+        }
+
+        ui.separator();
+        ui.heading("Health Checks");
+        ui.separator();
+
+        let mut hostname_idx_to_delete = None;
+        let simulation_started = self.state != ServerState::SimulationNotStarted;
+
+        for idx in 0..self.health_checks.hostnames.len() {
+            let is_up = self.is_upstream_up(&self.health_checks.hostnames[idx]);
+
+            ui.horizontal(|ui| {
+                ui.text_edit_label_toggle(editable, &mut self.health_checks.hostnames[idx]);
+
+                if simulation_started {
+                    if is_up {
+                        ui.colored_label(Color32::GREEN, "Up");
+                    } else {
+                        ui.colored_label(ERROR_COLOR, "Down");
+                    }
+                }
+
+                if editable && ui.button("Delete").clicked() {
+                    hostname_idx_to_delete = Some(idx);
+                }
+            });
+        }
+
+        if let Some(idx) = hostname_idx_to_delete {
+            self.health_checks.hostnames.remove(idx);
+        }
+
+        if editable && ui.button("Add upstream").clicked() {
+            self.health_checks.hostnames.push(String::new());
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Interval:");
+
+            if editable {
+                ui.add(
+                    egui::DragValue::new(&mut self.health_checks.interval_ms)
+                        .clamp_range(100..=10_000)
+                        .suffix("ms"),
+                );
+            } else {
+                ui.label(format!("{}ms", self.health_checks.interval_ms));
+            }
+        });
+
+        ui.separator();
+        ui.heading("Rate Limit");
+        ui.separator();
+
+        ui.add_enabled_ui(editable, |ui| {
+            let mut limited = self.rate_limit.is_some();
+
+            if ui.checkbox(&mut limited, "Limit requests").changed() {
+                self.rate_limit = limited.then(RateLimit::default);
+            }
+
+            let Some(rate_limit) = &mut self.rate_limit else {
+                return;
+            };
+
+            ui.horizontal(|ui| {
+                ui.label("Per:");
+
+                if ui
+                    .radio(rate_limit.key == RateLimitKey::Sender, "Sender")
+                    .clicked()
+                {
+                    rate_limit.key = RateLimitKey::Sender;
+                }
+
+                if ui
+                    .radio(matches!(rate_limit.key, RateLimitKey::Header(_)), "Header")
+                    .clicked()
+                    && rate_limit.key == RateLimitKey::Sender
+                {
+                    rate_limit.key = RateLimitKey::Header("X-Api-Key".to_string());
+                }
+
+                if let RateLimitKey::Header(name) = &mut rate_limit.key {
+                    ui.text_edit_singleline(name);
+                }
+            });
+
+            ui.horizontal(|ui| {
+                let is_token_bucket =
+                    matches!(rate_limit.algorithm, RateLimitAlgorithm::TokenBucket { .. });
+
+                if ui.radio(is_token_bucket, "Token bucket").clicked() && !is_token_bucket {
+                    rate_limit.algorithm = RateLimit::default().algorithm;
+                }
+
+                if ui.radio(!is_token_bucket, "Sliding window").clicked() && is_token_bucket {
+                    rate_limit.algorithm = RateLimitAlgorithm::SlidingWindow {
+                        limit: 5,
+                        window_ms: 1000,
+                    };
+                }
+            });
+
+            ui.horizontal(|ui| match &mut rate_limit.algorithm {
+                RateLimitAlgorithm::TokenBucket {
+                    capacity,
+                    refill_per_second,
+                } => {
+                    ui.label("Capacity:");
+                    ui.add(egui::DragValue::new(capacity).clamp_range(1..=1000));
+                    ui.label("Refill:");
+                    ui.add(
+                        egui::DragValue::new(refill_per_second)
+                            .speed(0.1)
+                            .clamp_range(0.1..=1000.0)
+                            .suffix("/s"),
+                    );
+                }
+                RateLimitAlgorithm::SlidingWindow { limit, window_ms } => {
+                    ui.label("Limit:");
+                    ui.add(egui::DragValue::new(limit).clamp_range(1..=1000));
+                    ui.label("Window:");
+                    ui.add(
+                        egui::DragValue::new(window_ms)
+                            .clamp_range(100..=60_000)
+                            .suffix("ms"),
+                    );
+                }
+            });
+        });
+
+        if self.num_throttled() > 0 {
+            ui.label(format!("Throttled requests: {}", self.num_throttled()));
+        }
+
+        if self.circuit_breakers().next().is_some() {
+            ui.separator();
+            ui.heading("Circuit Breakers");
+            ui.separator();
+
+            for (hostname, breaker) in self.circuit_breakers() {
+                ui.horizontal(|ui| {
+                    ui.label(hostname);
+
+                    match breaker.state {
+                        CircuitState::Closed if breaker.failures > 0 => {
+                            ui.label(format!("Closed ({} failures)", breaker.failures))
+                        }
+                        CircuitState::Closed => ui.label("Closed"),
+                        CircuitState::Open => ui.colored_label(ERROR_COLOR, "Open"),
+                        CircuitState::HalfOpen => ui.colored_label(Color32::YELLOW, "Half-open"),
+                    };
+                });
+            }
+        }
+    }
+}
+
+fn expectations_results_ui(ui: &mut egui::Ui, expectations_results: &[(bool, String)]) {
+    for (passed, message) in expectations_results.iter() {
+        let mut job = LayoutJob::default();
+        let (icon, color) = if *passed {
+            ("✔", Color32::GREEN)
+        } else {
+            ("✖", Color32::RED)
+        };
+        job.append(
+            icon,
+            0.0,
+            TextFormat {
+                color,
+                ..Default::default()
+            },
+        );
+        job.append(message, 0.0, TextFormat::default());
+        ui.label(job);
     }
 }
 
 impl View for Database {
     fn ui(&mut self, ui: &mut egui::Ui, _editable: bool) {
+        if self.is_down() {
+            ui.colored_label(ERROR_COLOR, "Down");
+        } else if self.read_only {
+            ui.colored_label(ERROR_COLOR, "Read-only");
+        }
+
+        if !self.expectations_results.is_empty() {
+            ui.separator();
+            ui.heading("Expectations");
+            expectations_results_ui(ui, &self.expectations_results);
+        }
+
         ui.separator();
         ui.heading("Documents");
 
@@ -1,3 +1,5 @@
+use std::{collections::HashMap, time::Duration};
+
 use bevy::{
     ecs::system::SystemParam, math::Vec3Swizzles, prelude::*, sprite::MaterialMesh2dBundle,
 };
@@ -19,10 +21,15 @@ use crate::{
     game_state::AppState,
     layer,
     level::{ClientConfig, CurrentLevel, DatabaseConfig, ServerConfig},
+    level_data::render_requests,
     node::{
-        client::Client, database::Database, server::Server, Hostname, NodeConnections, NodeType,
-        SystemNodeBundle,
+        client::{Client, RequestConfig},
+        database::Database,
+        rate_limit::RateLimit,
+        server::Server,
+        Hostname, Locked, NodeConnections, NodeType, SystemNodeBundle,
     },
+    progress::{Design, DesignComponent, DesignNode, Progress},
     EditSet,
 };
 
@@ -116,6 +123,7 @@ struct GridRoot;
 fn spawn_grid(
     mut commands: Commands,
     current_level: CurrentLevel,
+    progress: Res<Progress>,
     asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
@@ -147,19 +155,48 @@ fn spawn_grid(
     if let Some((_, level)) = current_level.get() {
         println!("{:?}", level);
 
+        let mut restore = DesignRestore::new(current_level.id().and_then(|id| progress.design(id)));
+
+        // Generated afresh every time the level is opened, from the seed of the level.
+        let data = level.generate_data();
+
+        let is_locked = |name: &str| {
+            level
+                .constraints
+                .as_ref()
+                .is_some_and(|constraints| constraints.locked_nodes.iter().any(|n| n == name))
+        };
+
+        // The entity and position of every node of the level, by name, to connect them.
+        let mut level_nodes = HashMap::new();
+
+        let render_requests = |name: &str, request_configs: &[RequestConfig]| {
+            render_requests(data.as_ref(), request_configs).unwrap_or_else(|error| {
+                error!("Failed to render the requests of Client `{name}`: {error}");
+                request_configs.to_vec()
+            })
+        };
+
         for ClientConfig {
             name,
             x,
             y,
             request_configs,
+            hidden_request_configs,
+            expectations,
         } in level.clients.iter()
         {
             let client = Client::new()
                 .editable(false)
-                .request_configs(request_configs.to_vec());
+                .request_configs(render_requests(name, request_configs))
+                .hidden_request_configs(render_requests(name, hidden_request_configs))
+                .expectations(expectations.clone());
 
             let system_bundle = SystemNodeBundle::new(NodeType::Client).node_name(name.into());
 
+            let saved_node = restore.claim(NodeType::Client, name);
+            let (x, y) = saved_node.map_or((*x, *y), |(_, node)| (node.x, node.y));
+
             let component_entity = create_component(
                 &mut commands,
                 &asset_server,
@@ -167,27 +204,55 @@ fn spawn_grid(
                 &mut materials,
                 system_bundle,
                 AddComponentPayload::Client(client),
-                *x,
-                *y,
+                x,
+                y,
             );
 
+            if is_locked(name) {
+                commands.entity(component_entity).insert(Locked);
+            }
+
+            level_nodes
+                .entry(name.as_str())
+                .or_insert((component_entity, Vec2::new(x, y)));
+            restore.set_entity(saved_node, component_entity);
             children.push(component_entity);
         }
 
-        for DatabaseConfig {
-            name,
-            x,
-            y,
-            initial_documents,
-            hostname,
-        } in level.databases.iter()
-        {
+        for database_config in level.databases.iter() {
+            let DatabaseConfig {
+                name,
+                x,
+                y,
+                initial_documents,
+                hostname,
+                ..
+            } = database_config;
+
+            let documents = database_config
+                .documents(data.as_ref())
+                .unwrap_or_else(|error| {
+                    error!("Failed to generate the documents of Database `{name}`: {error}");
+                    initial_documents.to_vec()
+                });
+
+            let expectations = database_config
+                .render_expectations(data.as_ref())
+                .unwrap_or_else(|error| {
+                    error!("Failed to render the expectations of Database `{name}`: {error}");
+                    database_config.expectations.clone()
+                });
+
             let database = Database::new()
                 .editable(false)
-                .initial_documents(initial_documents.to_vec());
+                .initial_documents(documents)
+                .expectations(expectations);
 
             let system_bundle = SystemNodeBundle::new(NodeType::Database).node_name(name.into());
 
+            let saved_node = restore.claim(NodeType::Database, name);
+            let (x, y) = saved_node.map_or((*x, *y), |(_, node)| (node.x, node.y));
+
             let component_entity = create_component(
                 &mut commands,
                 &asset_server,
@@ -195,10 +260,18 @@ fn spawn_grid(
                 &mut materials,
                 system_bundle,
                 AddComponentPayload::Database(Hostname(hostname.to_string()), database),
-                *x,
-                *y,
+                x,
+                y,
             );
 
+            if is_locked(name) {
+                commands.entity(component_entity).insert(Locked);
+            }
+
+            level_nodes
+                .entry(name.as_str())
+                .or_insert((component_entity, Vec2::new(x, y)));
+            restore.set_entity(saved_node, component_entity);
             children.push(component_entity);
         }
 
@@ -207,31 +280,238 @@ fn spawn_grid(
             x,
             y,
             max_concurrent_connections,
+            cpu_cost,
+            base_cost_ms,
+            step_cost_ms,
+            workers,
+            max_backlog,
+            hostname,
+            endpoints,
+            ..
         } in level.servers.iter()
         {
-            let server =
-                Server::default().with_max_concurrent_connections(*max_concurrent_connections);
+            let mut server = Server::default()
+                .with_max_concurrent_connections(*max_concurrent_connections)
+                .with_cpu_cost(*cpu_cost)
+                .with_processing_costs(
+                    Duration::from_millis(*base_cost_ms),
+                    Duration::from_millis(*step_cost_ms),
+                )
+                .with_worker_pool(*workers, *max_backlog);
+            let mut hostname = Hostname(hostname.clone());
+
+            if !endpoints.is_empty() {
+                server.endpoint_handlers = endpoints.clone();
+            }
 
             let system_bundle = SystemNodeBundle::new(NodeType::Server).node_name(name.into());
 
+            let saved_node = restore.claim(NodeType::Server, name);
+            let (x, y) = saved_node.map_or((*x, *y), |(_, node)| (node.x, node.y));
+
+            if let Some((
+                _,
+                DesignNode {
+                    component:
+                        DesignComponent::Server {
+                            hostname: saved_hostname,
+                            endpoints,
+                            middlewares,
+                            health_checks,
+                            rate_limit,
+                        },
+                    ..
+                },
+            )) = saved_node
+            {
+                hostname = Hostname(saved_hostname.clone());
+                // The locked endpoints always come from the level, in case it has changed since
+                // the design was saved.
+                server.endpoint_handlers.retain(|endpoint| endpoint.locked);
+                server.endpoint_handlers.extend(
+                    endpoints
+                        .iter()
+                        .filter(|endpoint| !endpoint.locked)
+                        .cloned(),
+                );
+                server.middlewares = middlewares.clone();
+                server.health_checks = health_checks.clone();
+                server.rate_limit = rate_limit.clone().filter(RateLimit::is_valid);
+            }
+
+            let component_entity = create_component(
+                &mut commands,
+                &asset_server,
+                &mut meshes,
+                &mut materials,
+                system_bundle,
+                AddComponentPayload::Server(hostname, server),
+                x,
+                y,
+            );
+
+            if is_locked(name) {
+                commands.entity(component_entity).insert(Locked);
+            }
+
+            level_nodes
+                .entry(name.as_str())
+                .or_insert((component_entity, Vec2::new(x, y)));
+            restore.set_entity(saved_node, component_entity);
+            children.push(component_entity);
+        }
+
+        // Nodes the player added to the level.
+        for (idx, node) in restore.unclaimed() {
+            let component = match &node.component {
+                DesignComponent::Client { request_configs } => AddComponentPayload::Client(
+                    Client::new().request_configs(request_configs.clone()),
+                ),
+                DesignComponent::Server {
+                    hostname,
+                    endpoints,
+                    middlewares,
+                    health_checks,
+                    rate_limit,
+                } => {
+                    let mut server = Server::default();
+                    server.endpoint_handlers = endpoints.clone();
+                    server.middlewares = middlewares.clone();
+                    server.health_checks = health_checks.clone();
+                    server.rate_limit = rate_limit.clone().filter(RateLimit::is_valid);
+
+                    AddComponentPayload::Server(Hostname(hostname.clone()), server)
+                }
+                DesignComponent::Database { hostname } => {
+                    AddComponentPayload::Database(Hostname(hostname.clone()), Database::new())
+                }
+            };
+
+            let system_bundle =
+                SystemNodeBundle::new(node.node_type()).node_name(node.name.clone());
+
             let component_entity = create_component(
                 &mut commands,
                 &asset_server,
                 &mut meshes,
                 &mut materials,
                 system_bundle,
-                AddComponentPayload::Server(Hostname::default(), server),
-                *x,
-                *y,
+                component,
+                node.x,
+                node.y,
             );
 
+            restore.set_entity(Some((idx, node)), component_entity);
             children.push(component_entity);
         }
+
+        for (start, end) in restore.connections() {
+            children.push(spawn_connection(&mut commands, start, end));
+        }
+
+        // A saved design has its own connections, which may differ from those of the level.
+        if !restore.has_design() {
+            for server_config in level.servers.iter() {
+                for other in server_config.connections.iter() {
+                    match (
+                        level_nodes.get(server_config.name.as_str()),
+                        level_nodes.get(other.as_str()),
+                    ) {
+                        (Some(start), Some(end)) => {
+                            children.push(spawn_connection(&mut commands, *start, *end));
+                        }
+                        _ => error!(
+                            "Failed to connect Server `{}` to unknown node `{other}`",
+                            server_config.name
+                        ),
+                    }
+                }
+            }
+        }
     }
 
     commands.entity(grid_root_entity).push_children(&children);
 }
 
+// Matches the nodes of a level to those of the design it was last left with, so the player's
+// positions, edits and connections are restored.
+struct DesignRestore<'a> {
+    design: Option<&'a Design>,
+    entities: Vec<Option<Entity>>,
+    claimed: Vec<bool>,
+}
+
+impl<'a> DesignRestore<'a> {
+    fn new(design: Option<&'a Design>) -> Self {
+        let num_nodes = design.map_or(0, |design| design.nodes.len());
+
+        Self {
+            design,
+            entities: vec![None; num_nodes],
+            claimed: vec![false; num_nodes],
+        }
+    }
+
+    // The first saved node of the given type and name that has not been claimed yet.
+    fn claim(&mut self, node_type: NodeType, name: &str) -> Option<(usize, &'a DesignNode)> {
+        let (idx, node) = self.design?.nodes.iter().enumerate().find(|(idx, node)| {
+            !self.claimed[*idx] && node.node_type() == node_type && node.name == name
+        })?;
+
+        self.claimed[idx] = true;
+        Some((idx, node))
+    }
+
+    fn has_design(&self) -> bool {
+        self.design.is_some()
+    }
+
+    fn unclaimed(&mut self) -> Vec<(usize, &'a DesignNode)> {
+        let Some(design) = self.design else {
+            return vec![];
+        };
+
+        let unclaimed: Vec<_> = design
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !self.claimed[*idx])
+            .collect();
+
+        for (idx, _) in unclaimed.iter() {
+            self.claimed[*idx] = true;
+        }
+
+        unclaimed
+    }
+
+    fn set_entity(&mut self, node: Option<(usize, &DesignNode)>, entity: Entity) {
+        if let Some((idx, _)) = node {
+            self.entities[idx] = Some(entity);
+        }
+    }
+
+    // The entity and position of both ends of every saved connection.
+    fn connections(&self) -> Vec<((Entity, Vec2), (Entity, Vec2))> {
+        let Some(design) = self.design else {
+            return vec![];
+        };
+
+        design
+            .connections
+            .iter()
+            .filter_map(|(start, end)| {
+                let node_end = |idx: usize| {
+                    let node = design.nodes.get(idx)?;
+                    Some((self.entities[idx]?, Vec2::new(node.x, node.y)))
+                };
+
+                Some((node_end(*start)?, node_end(*end)?))
+            })
+            .collect()
+    }
+}
+
 fn destroy_grid(mut commands: Commands, grid_root: Query<Entity, With<GridRoot>>) {
     if let Ok(grid_root) = grid_root.get_single() {
         commands.entity(grid_root).despawn_recursive();
@@ -325,8 +605,13 @@ fn delete_node(
     mut commands: Commands,
     mut events: EventReader<DeleteNodeEvent>,
     mut remove_connections: RemoveConnectionSystemParam,
+    locked: Query<(), With<Locked>>,
 ) {
     for event in events.iter() {
+        if locked.contains(event.0) {
+            continue;
+        }
+
         remove_connections.remove_connections_by_node(event.0);
         commands.entity(event.0).despawn_recursive();
     }
@@ -340,12 +625,13 @@ fn snap_to_grid(position: Vec2, grid_size: f32) -> Vec2 {
 
 fn drag_node(
     mut drag_event: EventReader<ListenedEvent<Drag>>,
-    mut nodes_query: Query<&mut Transform>,
+    mut nodes_query: Query<&mut Transform, Without<Locked>>,
 ) {
     for drag_event in drag_event.iter() {
         if matches!(drag_event.button, PointerButton::Primary) && drag_event.delta != Vec2::ZERO {
-            let mut transform = nodes_query.get_mut(drag_event.target).unwrap();
-            transform.translation += Vec3::from((drag_event.delta, 0.0));
+            if let Ok(mut transform) = nodes_query.get_mut(drag_event.target) {
+                transform.translation += Vec3::from((drag_event.delta, 0.0));
+            }
         }
     }
 }
@@ -451,7 +737,45 @@ fn drag_end_node(
 }
 
 #[derive(Component)]
-struct ConnectedNodeConnectionLine(Entity, Entity);
+pub struct ConnectedNodeConnectionLine(pub Entity, pub Entity);
+
+fn spawn_connection(
+    commands: &mut Commands,
+    (start_node_entity, start): (Entity, Vec2),
+    (end_node_entity, end): (Entity, Vec2),
+) -> Entity {
+    let line_entity = commands
+        .spawn((
+            ShapeBundle {
+                path: GeometryBuilder::build_as(&shapes::Line(start, end)),
+                transform: Transform::from_xyz(0.0, 0.0, layer::CONNECTIONS),
+                ..default()
+            },
+            Stroke::new(Color::YELLOW, 5.0),
+            NodeConnectionLine,
+            ConnectedNodeConnectionLine(start_node_entity, end_node_entity),
+            OnPointer::<Click>::send_event::<ConnectionLineClickEvent>(),
+            PickableBundle::default(),
+            RaycastPickTarget::default(),
+        ))
+        .id();
+
+    // The nodes were only just spawned, so their connections are added once they exist.
+    commands.add(move |world: &mut World| {
+        let nodes = [
+            (start_node_entity, end_node_entity),
+            (end_node_entity, start_node_entity),
+        ];
+
+        for (node, other_node) in nodes {
+            if let Some(mut connections) = world.get_mut::<NodeConnections>(node) {
+                connections.add_connection(other_node, line_entity);
+            }
+        }
+    });
+
+    line_entity
+}
 
 fn pointer_up_node(
     mut commands: Commands,
@@ -511,7 +835,7 @@ fn pointer_up_node(
     }
 }
 
-struct ConnectionLineClickEvent(ListenedEvent<Click>);
+pub struct ConnectionLineClickEvent(pub ListenedEvent<Click>);
 
 impl From<ListenedEvent<Click>> for ConnectionLineClickEvent {
     fn from(value: ListenedEvent<Click>) -> Self {
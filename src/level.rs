@@ -1,16 +1,25 @@
+use std::path::PathBuf;
+
 use bevy::{
     ecs::system::SystemParam,
-    prelude::{Assets, Res, Resource},
+    prelude::{Assets, Handle, Res, Resource},
     reflect::TypeUuid,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    node::{client::RequestConfig, database::Document},
+    chaos::ChaosEvent,
+    level_data::{DataGenerator, GeneratedData},
+    node::{
+        client::{ClientExpectation, RequestConfig},
+        database::{DatabaseExpectation, Document},
+        server::Endpoint,
+    },
+    score::ScoreBreakdown,
     Handles,
 };
 
-#[derive(Deserialize, Debug, TypeUuid)]
+#[derive(Serialize, Deserialize, Debug, TypeUuid)]
 #[uuid = "F542117A-81DB-43E1-BB4C-4B4130B440C5"]
 pub struct Level {
     pub name: String,
@@ -18,37 +27,215 @@ pub struct Level {
     pub clients: Vec<ClientConfig>,
     pub databases: Vec<DatabaseConfig>,
     pub servers: Vec<ServerConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scoring: Option<Scoring>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<Pricing>,
+    // The most the system may cost, if the level has pricing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<u32>,
+    // The ids of the levels that must be completed before this one is unlocked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prerequisites: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<DataGenerator>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constraints: Option<Constraints>,
+    // Failures injected into the system while it is simulated, in simulation time.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chaos: Vec<ChaosEvent>,
+}
+
+impl Level {
+    /// Generates the data of the level, if it has any.
+    pub fn generate_data(&self) -> Option<GeneratedData> {
+        self.data.as_ref().map(DataGenerator::generate)
+    }
+}
+
+/// The levels of the game or of a level pack, in the order they are played. Level files are
+/// relative to the directory of the manifest.
+#[derive(Deserialize, Debug, TypeUuid)]
+#[uuid = "2D0E7B4E-4C1C-4E43-9F55-4A2B0C4D9E31"]
+pub struct LevelManifest {
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Chapter {
+    pub name: String,
+    pub levels: Vec<LevelEntry>,
 }
 
 #[derive(Deserialize, Debug)]
+pub struct LevelEntry {
+    // Identifies the level in saved progress and prerequisites, so must stay the same when the
+    // level file is renamed.
+    pub id: String,
+    pub file: String,
+}
+
+/// A level found in a manifest.
+#[derive(Debug)]
+pub struct LevelInfo {
+    pub id: String,
+    pub chapter: String,
+    pub handle: Handle<Level>,
+    // The path of the level file, relative to the assets directory unless it is in a level pack.
+    pub path: PathBuf,
+}
+
+/// What each part of a system costs. Nodes and connections are paid for up front, and requests
+/// as they are made during the simulation.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct Pricing {
+    pub client: u32,
+    pub server: u32,
+    pub database: u32,
+    pub connection: u32,
+    // Per request or Database call received by a Server or Database.
+    pub request: u32,
+}
+
+/// Limits on what can be built in a level. Nodes placed by the level count towards the limits.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct Constraints {
+    // The most nodes of each type the system may have.
+    pub max_clients: Option<usize>,
+    pub max_servers: Option<usize>,
+    pub max_databases: Option<usize>,
+    // The fewest connections between any Client and any Database.
+    pub min_database_hops: Option<usize>,
+    // The number of Servers that must be connected to Clients.
+    pub public_servers: Option<usize>,
+    // Pairs of node names that must, or must not, be connected.
+    pub required_connections: Vec<(String, String)>,
+    pub forbidden_connections: Vec<(String, String)>,
+    // The names of nodes placed by the level that cannot be moved or deleted.
+    pub locked_nodes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Scoring {
+    #[serde(default)]
+    pub weights: ScoreWeights,
+    // The highest scores that earn 1, 2 and 3 stars.
+    pub star_thresholds: [u32; 3],
+}
+
+/// The points added to a solution's score for each unit of what it uses; lower scores are better.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct ScoreWeights {
+    pub per_node: f32,
+    pub per_handler_char: f32,
+    pub per_latency_ms: f32,
+    pub per_message: f32,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            per_node: 100.0,
+            per_handler_char: 1.0,
+            per_latency_ms: 0.1,
+            per_message: 10.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ClientConfig {
     pub name: String,
     pub x: f32,
     pub y: f32,
     pub request_configs: Vec<RequestConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hidden_request_configs: Vec<RequestConfig>,
+    // What every response of the Client, together, must be once it has finished.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expectations: Vec<ClientExpectation>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ServerConfig {
     pub name: String,
     pub x: f32,
     pub y: f32,
     pub max_concurrent_connections: Option<u8>,
+    // Simulated milliseconds of processing per 1,000 handler statements run; slower servers hold
+    // their connections for longer.
+    pub cpu_cost: Option<f32>,
+    // Simulated processing time added to the first step of every request, and to every step.
+    #[serde(default)]
+    pub base_cost_ms: u64,
+    #[serde(default)]
+    pub step_cost_ms: u64,
+    // The number of steps the server can process at once, and how many more may wait for a
+    // worker before new requests are rejected.
+    pub workers: Option<u8>,
+    pub max_backlog: Option<u8>,
+    // Servers are built by the player unless the level gives them a hostname or endpoints.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hostname: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub endpoints: Vec<Endpoint>,
+    // The names of the nodes the server is connected to when the level is first opened.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub connections: Vec<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct DatabaseConfig {
     pub name: String,
     pub x: f32,
     pub y: f32,
     pub initial_documents: Vec<Document>,
     pub hostname: String,
+    // A generated collection, whose documents are added to the initial documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated_documents: Option<String>,
+    // What the documents must be once every Client has finished.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expectations: Vec<DatabaseExpectation>,
+}
+
+impl DatabaseConfig {
+    pub fn documents(&self, data: Option<&GeneratedData>) -> Result<Vec<Document>, String> {
+        let mut documents = self.initial_documents.clone();
+
+        if let Some(collection) = &self.generated_documents {
+            let data = data
+                .ok_or_else(|| format!("The level has no data to generate `{collection}` from."))?;
+
+            documents.extend(data.documents(collection)?);
+        }
+
+        Ok(documents)
+    }
+
+    pub fn render_expectations(
+        &self,
+        data: Option<&GeneratedData>,
+    ) -> Result<Vec<DatabaseExpectation>, String> {
+        match data {
+            Some(data) => self
+                .expectations
+                .iter()
+                .map(|expectation| data.render_database_expectation(expectation))
+                .collect(),
+            None => Ok(self.expectations.clone()),
+        }
+    }
 }
 
 #[derive(Resource, Default)]
 pub struct LevelState {
     pub current_level: Option<usize>,
     pub level_passed: bool,
+    pub score: Option<ScoreBreakdown>,
 }
 
 #[derive(SystemParam)]
@@ -60,8 +247,17 @@ pub struct CurrentLevel<'w> {
 
 impl<'w> CurrentLevel<'w> {
     pub fn get(&self) -> Option<(usize, &Level)> {
+        self.level_state.current_level.map(|idx| {
+            (
+                idx,
+                self.levels.get(&self.handles.levels[idx].handle).unwrap(),
+            )
+        })
+    }
+
+    pub fn id(&self) -> Option<&str> {
         self.level_state
             .current_level
-            .map(|idx| (idx, self.levels.get(&self.handles.levels[idx]).unwrap()))
+            .map(|idx| self.handles.levels[idx].id.as_str())
     }
 }
@@ -0,0 +1,364 @@
+use std::collections::{BTreeMap, HashSet};
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::node::{
+    client::{RequestConfig, ResponseExpectation},
+    database::{DatabaseExpectation, Document},
+};
+
+/// Generates the data of a level from a seed, so that solutions have to work with whatever data
+/// they are given rather than with the values in the level file. Requests and expectations refer
+/// to the data with placeholders, e.g. `{{users.0.name}}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DataGenerator {
+    pub seed: u64,
+    // Collections are generated in the order of their names, so the same seed always generates the
+    // same data.
+    pub collections: BTreeMap<String, CollectionGenerator>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CollectionGenerator {
+    pub count: usize,
+    pub fields: BTreeMap<String, FieldGenerator>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum FieldGenerator {
+    // A random integer that is unique within the collection.
+    Id,
+    // A random integer between the two bounds, inclusive.
+    Int(i64, i64),
+    Name,
+    OneOf(Vec<Value>),
+}
+
+const NAMES: &[&str] = &[
+    "Alice", "Bob", "Charlie", "Dana", "Eve", "Frank", "Grace", "Heidi", "Ivan", "Judy", "Karl",
+    "Liam", "Mallory", "Nina", "Oscar", "Peggy", "Quinn", "Rupert", "Sybil", "Trent", "Uma",
+    "Victor", "Wendy", "Xavier", "Yara", "Zoe",
+];
+
+impl DataGenerator {
+    pub fn generate(&self) -> GeneratedData {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+
+        let collections = self
+            .collections
+            .iter()
+            .map(|(name, collection)| (name.clone(), Value::Array(collection.generate(&mut rng))))
+            .collect();
+
+        GeneratedData(Value::Object(collections))
+    }
+
+    /// Finds the fields that cannot be generated.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+
+        for (name, collection) in self.collections.iter() {
+            for (field, generator) in collection.fields.iter() {
+                match generator {
+                    FieldGenerator::Int(min, max) if min > max => errors.push(format!(
+                        "Field `{field}` of collection `{name}` has a minimum greater than its maximum."
+                    )),
+                    FieldGenerator::OneOf(values) if values.is_empty() => errors.push(format!(
+                        "Field `{field}` of collection `{name}` has no values to choose from."
+                    )),
+                    _ => {}
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+impl CollectionGenerator {
+    fn generate(&self, rng: &mut ChaCha8Rng) -> Vec<Value> {
+        // Ids are drawn from a range much larger than the collection, so they cannot be guessed.
+        let max_id = (self.count as i64 * 10).max(1000);
+        let mut ids = HashSet::new();
+
+        (0..self.count)
+            .map(|_| {
+                let document: Map<String, Value> = self
+                    .fields
+                    .iter()
+                    .map(|(field, generator)| {
+                        let value = match generator {
+                            FieldGenerator::Id => loop {
+                                let id = rng.gen_range(1..=max_id);
+
+                                if ids.insert(id) {
+                                    break Value::from(id);
+                                }
+                            },
+                            FieldGenerator::Int(min, max) => {
+                                Value::from(rng.gen_range(*min..=*max.max(min)))
+                            }
+                            FieldGenerator::Name => Value::from(*NAMES.choose(rng).unwrap()),
+                            FieldGenerator::OneOf(values) => {
+                                values.choose(rng).cloned().unwrap_or_default()
+                            }
+                        };
+
+                        (field.clone(), value)
+                    })
+                    .collect();
+
+                Value::Object(document)
+            })
+            .collect()
+    }
+}
+
+/// The generated collections of a level, by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedData(Value);
+
+impl GeneratedData {
+    pub fn documents(&self, collection: &str) -> Result<Vec<Document>, String> {
+        match self.lookup(collection)? {
+            Value::Array(documents) => documents
+                .iter()
+                .map(|document| match document {
+                    Value::Object(document) => Ok(document.clone()),
+                    _ => Err(format!("`{collection}` is not a collection of documents.")),
+                })
+                .collect(),
+            _ => Err(format!("`{collection}` is not a collection.")),
+        }
+    }
+
+    // Finds the value of a placeholder, e.g. `users.0.name`.
+    fn lookup(&self, expression: &str) -> Result<&Value, String> {
+        expression
+            .trim()
+            .split('.')
+            .try_fold(&self.0, |value, key| match value {
+                Value::Array(elems) => key.parse::<usize>().ok().and_then(|idx| elems.get(idx)),
+                Value::Object(map) => map.get(key),
+                _ => None,
+            })
+            .ok_or_else(|| format!("`{{{{{expression}}}}}` does not refer to any generated data."))
+    }
+
+    // A string that is a single placeholder is replaced by the value it refers to, keeping its
+    // type.
+    fn placeholder(text: &str) -> Option<&str> {
+        text.strip_prefix("{{")?
+            .strip_suffix("}}")
+            .filter(|expression| !expression.contains("{{") && !expression.contains("}}"))
+    }
+
+    // Replaces the placeholders in text. Strings are inserted as they are, and other values as
+    // JSON.
+    fn render_str(&self, text: &str) -> Result<String, String> {
+        let mut rendered = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start..].find("}}") else {
+                break;
+            };
+
+            rendered.push_str(&rest[..start]);
+
+            match self.lookup(&rest[start + 2..start + len])? {
+                Value::String(value) => rendered.push_str(value),
+                value => rendered.push_str(&value.to_string()),
+            }
+
+            rest = &rest[start + len + 2..];
+        }
+
+        rendered.push_str(rest);
+
+        Ok(rendered)
+    }
+
+    fn render_value(&self, value: &Value) -> Result<Value, String> {
+        match value {
+            Value::String(text) => match Self::placeholder(text) {
+                Some(expression) => self.lookup(expression).cloned(),
+                None => self.render_str(text).map(Value::String),
+            },
+            Value::Array(elems) => elems
+                .iter()
+                .map(|elem| self.render_value(elem))
+                .collect::<Result<_, _>>()
+                .map(Value::Array),
+            Value::Object(map) => map
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), self.render_value(value)?)))
+                .collect::<Result<_, String>>()
+                .map(Value::Object),
+            value => Ok(value.clone()),
+        }
+    }
+
+    fn render_expectation(
+        &self,
+        expectation: &ResponseExpectation,
+    ) -> Result<ResponseExpectation, String> {
+        Ok(match expectation {
+            ResponseExpectation::Status(status) => ResponseExpectation::Status(*status),
+            ResponseExpectation::ExactBody(body) => {
+                ResponseExpectation::ExactBody(self.render_value(body)?)
+            }
+            // A placeholder for a whole collection adds each of its documents to the set.
+            ResponseExpectation::ExactSet(elems) => {
+                let mut rendered = vec![];
+
+                for elem in elems {
+                    match (
+                        elem.as_str().and_then(Self::placeholder),
+                        self.render_value(elem)?,
+                    ) {
+                        (Some(_), Value::Array(values)) => rendered.extend(values),
+                        (_, value) => rendered.push(value),
+                    }
+                }
+
+                ResponseExpectation::ExactSet(rendered)
+            }
+            ResponseExpectation::Header(name, value) => {
+                ResponseExpectation::Header(name.clone(), self.render_str(value)?)
+            }
+        })
+    }
+
+    pub fn render_database_expectation(
+        &self,
+        expectation: &DatabaseExpectation,
+    ) -> Result<DatabaseExpectation, String> {
+        Ok(match expectation {
+            DatabaseExpectation::Contains(fields) => {
+                DatabaseExpectation::Contains(self.render_value(fields)?)
+            }
+            DatabaseExpectation::Count(count) => DatabaseExpectation::Count(*count),
+            DatabaseExpectation::Absent(id) => DatabaseExpectation::Absent(self.render_value(id)?),
+        })
+    }
+
+    pub fn render_request(&self, request_config: &RequestConfig) -> Result<RequestConfig, String> {
+        let mut rendered = request_config.clone();
+        rendered.url = self.render_str(&request_config.url)?;
+        rendered.path = self.render_str(&request_config.path)?;
+        rendered.body = self.render_str(&request_config.body)?;
+        *rendered.expectations_mut() = request_config
+            .expectations()
+            .iter()
+            .map(|expectation| self.render_expectation(expectation))
+            .collect::<Result<_, _>>()?;
+
+        Ok(rendered)
+    }
+}
+
+/// Replaces the placeholders of requests with the generated data, if the level has any.
+pub fn render_requests(
+    data: Option<&GeneratedData>,
+    request_configs: &[RequestConfig],
+) -> Result<Vec<RequestConfig>, String> {
+    match data {
+        Some(data) => request_configs
+            .iter()
+            .map(|request_config| data.render_request(request_config))
+            .collect(),
+        None => Ok(request_configs.to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn generator(seed: u64) -> DataGenerator {
+        DataGenerator {
+            seed,
+            collections: BTreeMap::from([(
+                "users".to_string(),
+                CollectionGenerator {
+                    count: 3,
+                    fields: BTreeMap::from([
+                        ("id".to_string(), FieldGenerator::Id),
+                        ("name".to_string(), FieldGenerator::Name),
+                        ("age".to_string(), FieldGenerator::Int(18, 90)),
+                    ]),
+                },
+            )]),
+        }
+    }
+
+    #[test]
+    fn should_generate_same_data_from_same_seed() {
+        let data = generator(7).generate();
+        let users = data.documents("users").unwrap();
+
+        assert_eq!(data, generator(7).generate());
+        assert_eq!(3, users.len());
+        assert!(users.iter().all(|user| user["id"].is_i64()
+            && user["name"].is_string()
+            && (18..=90).contains(&user["age"].as_i64().unwrap())));
+        assert_eq!(
+            3,
+            users
+                .iter()
+                .map(|user| user["id"].as_i64())
+                .collect::<HashSet<_>>()
+                .len()
+        );
+    }
+
+    #[test]
+    fn should_render_placeholders_from_generated_data() {
+        let data = generator(7).generate();
+        let users = data.documents("users").unwrap();
+        let user = &users[1];
+
+        let mut request_config: RequestConfig = serde_json::from_value(json!({
+            "url": "users-api",
+            "path": "/users/{{users.1.id}}",
+            "method": "Post",
+            "body": "{ \"name\": \"{{users.1.name}}\", \"user\": {{users.1}} }",
+            "trace_id": "F94A3B4B-B228-4B68-B554-FB044E5A9C3A",
+            "response": null,
+            "expectations": [],
+            "expectations_results": []
+        }))
+        .unwrap();
+        *request_config.expectations_mut() = vec![
+            ResponseExpectation::ExactBody(
+                json!({ "name": "{{users.1.name}}", "age": "{{users.1.age}}" }),
+            ),
+            ResponseExpectation::ExactSet(vec![json!("{{users}}")]),
+        ];
+
+        let rendered = data.render_request(&request_config).unwrap();
+
+        assert_eq!(format!("/users/{}", user["id"]), rendered.path);
+        assert_eq!(
+            json!({ "name": user["name"], "user": user }),
+            serde_json::from_str::<Value>(&rendered.body).unwrap()
+        );
+        assert!(matches!(
+            &rendered.expectations()[0],
+            ResponseExpectation::ExactBody(body) if body == &json!({ "name": user["name"], "age": user["age"] })
+        ));
+        assert!(matches!(
+            &rendered.expectations()[1],
+            ResponseExpectation::ExactSet(elems) if elems.len() == 3
+        ));
+
+        request_config.path = "/users/{{users.3.id}}".to_string();
+        assert!(data.render_request(&request_config).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use std::{collections::HashSet, fs};
+
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+
+use crate::{
+    level::{ClientConfig, DatabaseConfig, Level, ServerConfig},
+    node::{
+        client::Client,
+        database::Database,
+        server::{Endpoint, Server},
+        Hostname, NodeType,
+    },
+};
+
+pub struct LevelEditorPlugin;
+
+impl Plugin for LevelEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelEditorState>();
+    }
+}
+
+/// The details of the level being authored in the level editor, beside the nodes on the grid.
+#[derive(Resource)]
+pub struct LevelEditorState {
+    pub name: String,
+    pub description: String,
+    // Where the level file is exported to.
+    pub path: String,
+    pub export_result: Option<Result<String, String>>,
+}
+
+impl Default for LevelEditorState {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            description: String::new(),
+            path: "new.level.ron".to_string(),
+            export_result: None,
+        }
+    }
+}
+
+/// A node on the level editor grid, as it is written to the level file.
+pub struct EditorNode<'a> {
+    pub node_type: NodeType,
+    pub name: &'a str,
+    pub position: Vec2,
+    pub hostname: Option<&'a Hostname>,
+    pub client: Option<&'a Client>,
+    pub server: Option<&'a Server>,
+    pub database: Option<&'a Database>,
+    // The names of the nodes it is connected to.
+    pub connections: Vec<&'a str>,
+}
+
+impl LevelEditorState {
+    fn validate(&self, nodes: &[EditorNode]) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("The level needs a name.".to_string());
+        }
+
+        let mut hostnames = HashSet::new();
+
+        for node in nodes {
+            if let Some(hostname) = node.hostname {
+                // Players give hostnames to the servers they build, so only databases need one.
+                if node.node_type == NodeType::Database && !hostname.is_valid() {
+                    return Err(format!("Database `{}` needs a hostname.", node.name));
+                }
+
+                if hostname.is_valid() && !hostnames.insert(&hostname.0) {
+                    return Err(format!(
+                        "Hostname `{}` is used by more than one node.",
+                        hostname.0
+                    ));
+                }
+            }
+
+            for (idx, request_config) in node
+                .client
+                .iter()
+                .flat_map(|client| client.request_configs.iter())
+                .enumerate()
+            {
+                if !request_config.is_body_valid() {
+                    return Err(format!(
+                        "Request {} of Client `{}` does not have a valid JSON body.",
+                        idx + 1,
+                        node.name
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds a level from the nodes on the grid. Servers keep their hostname and endpoints only
+    /// if they were given any, otherwise they are left for the player to build.
+    pub fn build_level(&self, nodes: &[EditorNode]) -> Result<Level, String> {
+        self.validate(nodes)?;
+
+        let mut level = Level {
+            name: self.name.trim().to_string(),
+            description: self.description.clone(),
+            clients: vec![],
+            databases: vec![],
+            servers: vec![],
+            scoring: None,
+            pricing: None,
+            budget: None,
+            prerequisites: vec![],
+            data: None,
+            constraints: None,
+            chaos: vec![],
+        };
+
+        // Connections between two Servers are only written to the first of them.
+        let mut server_connections = HashSet::new();
+
+        for node in nodes {
+            let name = node.name.to_string();
+            let (x, y) = (node.position.x, node.position.y);
+
+            if let Some(client) = node.client {
+                level.clients.push(ClientConfig {
+                    name,
+                    x,
+                    y,
+                    request_configs: client.request_configs.clone(),
+                    hidden_request_configs: vec![],
+                    expectations: client.get_expectations().to_vec(),
+                });
+            } else if let Some(database) = node.database {
+                level.databases.push(DatabaseConfig {
+                    name,
+                    x,
+                    y,
+                    initial_documents: database.get_initial_documents(),
+                    hostname: node.hostname.map(|h| h.0.clone()).unwrap_or_default(),
+                    generated_documents: None,
+                    expectations: database.get_expectations().to_vec(),
+                });
+            } else if let Some(server) = node.server {
+                let endpoints = if server.endpoint_handlers == [Endpoint::default()] {
+                    vec![]
+                } else {
+                    server.endpoint_handlers.clone()
+                };
+
+                let connections = node
+                    .connections
+                    .iter()
+                    .filter(|other| !server_connections.contains(&(**other, node.name)))
+                    .map(|other| other.to_string())
+                    .collect();
+
+                server_connections.extend(node.connections.iter().map(|other| (node.name, *other)));
+
+                level.servers.push(ServerConfig {
+                    name,
+                    x,
+                    y,
+                    max_concurrent_connections: server.max_concurrent_connections(),
+                    cpu_cost: server.cpu_cost(),
+                    base_cost_ms: server.base_cost().as_millis() as u64,
+                    step_cost_ms: server.step_cost().as_millis() as u64,
+                    workers: server.workers(),
+                    max_backlog: server.max_backlog(),
+                    hostname: node.hostname.map(|h| h.0.clone()).unwrap_or_default(),
+                    endpoints,
+                    connections,
+                });
+            }
+        }
+
+        Ok(level)
+    }
+
+    /// Writes the level built from the nodes on the grid to the export path.
+    pub fn export(&mut self, nodes: &[EditorNode]) {
+        let result = self.build_level(nodes).and_then(|level| {
+            let contents = level_to_ron(&level).map_err(|error| error.to_string())?;
+
+            fs::write(&self.path, contents)
+                .map(|_| format!("Exported level to {}", self.path))
+                .map_err(|error| format!("Failed to export level to {}: {error}", self.path))
+        });
+
+        self.export_result = Some(result);
+    }
+}
+
+pub fn level_to_ron(level: &Level) -> Result<String, ron::Error> {
+    ron::ser::to_string_pretty(level, PrettyConfig::new().struct_names(true))
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::node::{client::ResponseExpectation, database::Document};
+
+    use super::*;
+
+    fn editor_node<'a>(
+        node_type: NodeType,
+        name: &'a str,
+        hostname: Option<&'a Hostname>,
+    ) -> EditorNode<'a> {
+        EditorNode {
+            node_type,
+            name,
+            position: Vec2::new(100.0, -50.0),
+            hostname,
+            client: None,
+            server: None,
+            database: None,
+            connections: vec![],
+        }
+    }
+
+    #[test]
+    fn should_export_level_that_loads_back() {
+        let mut client = Client::new();
+        client.request_configs[0].url = "users-api".to_string();
+        client.request_configs[0]
+            .expectations_mut()
+            .push(ResponseExpectation::ExactBody(json!({ "id": 1 })));
+
+        let document: Document = serde_json::from_value(json!({ "id": 1, "name": "Ada" })).unwrap();
+        let database = Database::new().initial_documents(vec![document.clone()]);
+        let hostname = Hostname("users-db".to_string());
+
+        let state = LevelEditorState {
+            name: "Users".to_string(),
+            description: "Return \"Ada\".\nGood luck!".to_string(),
+            ..Default::default()
+        };
+
+        let nodes = [
+            EditorNode {
+                client: Some(&client),
+                ..editor_node(NodeType::Client, "user", None)
+            },
+            EditorNode {
+                database: Some(&database),
+                ..editor_node(NodeType::Database, "db", Some(&hostname))
+            },
+        ];
+
+        let ron = level_to_ron(&state.build_level(&nodes).unwrap()).unwrap();
+        let level: Level = ron::from_str(&ron).unwrap();
+
+        assert_eq!("Return \"Ada\".\nGood luck!", level.description);
+        assert_eq!("users-api", level.clients[0].request_configs[0].url);
+        assert_eq!(vec![document], level.databases[0].initial_documents);
+        assert_eq!("users-db", level.databases[0].hostname);
+        assert_eq!(100.0, level.databases[0].x);
+    }
+
+    #[test]
+    fn should_not_export_database_without_hostname() {
+        let database = Database::new();
+        let hostname = Hostname::default();
+
+        let state = LevelEditorState {
+            name: "Users".to_string(),
+            ..Default::default()
+        };
+
+        let nodes = [EditorNode {
+            database: Some(&database),
+            ..editor_node(NodeType::Database, "db", Some(&hostname))
+        }];
+
+        assert_eq!(
+            Err("Database `db` needs a hostname.".to_string()),
+            state.build_level(&nodes).map(|level| level.name)
+        );
+    }
+
+    #[test]
+    fn should_export_pre_built_servers_and_their_connections() {
+        let mut server = Server::default();
+        server.endpoint_handlers[0].path = "/users".to_string();
+        let api_hostname = Hostname("users-api".to_string());
+        let worker_hostname = Hostname::default();
+
+        let state = LevelEditorState {
+            name: "Users".to_string(),
+            ..Default::default()
+        };
+
+        let default_server = Server::default();
+
+        let nodes = [
+            EditorNode {
+                server: Some(&server),
+                connections: vec!["user", "worker"],
+                ..editor_node(NodeType::Server, "api", Some(&api_hostname))
+            },
+            EditorNode {
+                server: Some(&default_server),
+                connections: vec!["api"],
+                ..editor_node(NodeType::Server, "worker", Some(&worker_hostname))
+            },
+        ];
+
+        let level = state.build_level(&nodes).unwrap();
+
+        assert_eq!("users-api", level.servers[0].hostname);
+        assert_eq!("/users", level.servers[0].endpoints[0].path);
+        assert_eq!(vec!["user", "worker"], level.servers[0].connections);
+        assert!(level.servers[1].endpoints.is_empty());
+        assert!(level.servers[1].connections.is_empty());
+    }
+}
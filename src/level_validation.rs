@@ -0,0 +1,926 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{
+    chaos::Fault,
+    level::{Level, LevelManifest},
+    node::{database::DatabaseExpectation, NodeType},
+};
+
+pub const MANIFEST_FILE: &str = "manifest.ron";
+
+/// A problem with a level or manifest file, at a 1-based line and column when it is known.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(path: &Path, line: Option<usize>, message: String) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line,
+            column: None,
+            message,
+        }
+    }
+
+    fn from_ron_error(path: &Path, error: ron::error::SpannedError) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line: Some(error.position.line),
+            column: Some(error.position.col),
+            message: error.code.to_string(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())?;
+
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+
+        if let Some(column) = self.column {
+            write!(f, ":{column}")?;
+        }
+
+        write!(f, ": {}", self.message)
+    }
+}
+
+// Finds the lines of fields in RON source, to point diagnostics at them. Fields are expected to
+// start their own line, as they do in every level file.
+struct Source<'a> {
+    // Lines that start within a string or comment are `None`, as they cannot start a field.
+    lines: Vec<Option<&'a str>>,
+}
+
+impl<'a> Source<'a> {
+    fn new(source: Option<&'a str>) -> Self {
+        let Some(source) = source else {
+            return Self { lines: vec![] };
+        };
+
+        let code_lines = Self::code_lines(source);
+
+        Self {
+            lines: source
+                .lines()
+                .zip(code_lines)
+                .map(|(line, is_code)| is_code.then_some(line))
+                .collect(),
+        }
+    }
+
+    // Whether each line starts outside of any string or comment, such as a multi-line handler.
+    fn code_lines(source: &str) -> Vec<bool> {
+        enum State {
+            Code,
+            String,
+            // The number of `#`s that close the raw string.
+            RawString(usize),
+            LineComment,
+            // Block comments nest in RON.
+            BlockComment(usize),
+        }
+
+        let chars: Vec<char> = source.chars().collect();
+        let mut code_lines = vec![true];
+        let mut state = State::Code;
+        let mut idx = 0;
+
+        while idx < chars.len() {
+            let c = chars[idx];
+            let next = chars.get(idx + 1).copied();
+
+            match state {
+                State::Code => match c {
+                    '"' => state = State::String,
+                    '/' if next == Some('/') => state = State::LineComment,
+                    '/' if next == Some('*') => {
+                        state = State::BlockComment(1);
+                        idx += 1;
+                    }
+                    'r' if idx == 0 || !chars[idx - 1].is_alphanumeric() => {
+                        let hashes = chars[idx + 1..].iter().take_while(|c| **c == '#').count();
+
+                        if chars.get(idx + 1 + hashes) == Some(&'"') {
+                            state = State::RawString(hashes);
+                            idx += hashes + 1;
+                        }
+                    }
+                    _ => {}
+                },
+                State::String => match c {
+                    '\\' => idx += 1,
+                    '"' => state = State::Code,
+                    _ => {}
+                },
+                State::RawString(hashes) => {
+                    if c == '"'
+                        && chars[idx + 1..].iter().take_while(|c| **c == '#').count() >= hashes
+                    {
+                        state = State::Code;
+                        idx += hashes;
+                    }
+                }
+                State::LineComment => {
+                    if c == '\n' {
+                        state = State::Code;
+                    }
+                }
+                State::BlockComment(depth) => {
+                    if c == '*' && next == Some('/') {
+                        state = if depth == 1 {
+                            State::Code
+                        } else {
+                            State::BlockComment(depth - 1)
+                        };
+                        idx += 1;
+                    } else if c == '/' && next == Some('*') {
+                        state = State::BlockComment(depth + 1);
+                        idx += 1;
+                    }
+                }
+            }
+
+            if chars.get(idx) == Some(&'\n') {
+                code_lines.push(matches!(state, State::Code));
+            }
+
+            idx += 1;
+        }
+
+        code_lines
+    }
+
+    fn is_field(line: &str, field: &str) -> bool {
+        line.trim_start()
+            .strip_prefix(field)
+            .is_some_and(|rest| rest.trim_start().starts_with(':'))
+    }
+
+    // The line of the `n`th occurrence of a field, counting from the line `after`.
+    fn field_line(&self, field: &str, n: usize, after: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(after)
+            .filter(|(_, line)| line.is_some_and(|line| Self::is_field(line, field)))
+            .nth(n)
+            .map(|(idx, _)| idx + 1)
+    }
+
+    // The line of the `n`th occurrence of a field with the given string value.
+    fn field_value_line(&self, field: &str, value: &str, n: usize) -> Option<usize> {
+        let quoted = format!("\"{value}\"");
+
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                line.is_some_and(|line| Self::is_field(line, field) && line.contains(&quoted))
+            })
+            .nth(n)
+            .map(|(idx, _)| idx + 1)
+    }
+}
+
+pub fn parse_level(path: &Path, source: &str) -> Result<Level, Diagnostic> {
+    ron::from_str(source).map_err(|error| Diagnostic::from_ron_error(path, error))
+}
+
+pub fn parse_manifest(path: &Path, source: &str) -> Result<LevelManifest, Diagnostic> {
+    ron::from_str(source).map_err(|error| Diagnostic::from_ron_error(path, error))
+}
+
+/// Checks what parsing a level does not: the problems that would fail the level when it is
+/// played, or make it impossible to pass. The source is only used to find lines.
+pub fn validate_level(path: &Path, level: &Level, source: Option<&str>) -> Vec<Diagnostic> {
+    let source = Source::new(source);
+    let mut diagnostics = vec![];
+    let mut error = |line: Option<usize>, message: String| {
+        diagnostics.push(Diagnostic::new(path, line, message));
+    };
+
+    if level.clients.is_empty() {
+        error(
+            None,
+            "The level has no Clients, so can never be passed.".to_string(),
+        );
+    }
+
+    if let Some(data) = &level.data {
+        for message in data.validate() {
+            error(source.field_line("data", 0, 0), message);
+        }
+    }
+
+    let data = level.generate_data();
+
+    let database_hostnames: HashSet<_> = level
+        .databases
+        .iter()
+        .map(|database| database.hostname.as_str())
+        .collect();
+
+    let server_hostnames: HashSet<_> = level
+        .servers
+        .iter()
+        .map(|server| server.hostname.as_str())
+        .collect();
+
+    // Players can build Servers with any hostname, unless the level already places, with their
+    // hostnames, every Server it allows.
+    let are_hostnames_fixed = level
+        .constraints
+        .as_ref()
+        .and_then(|constraints| constraints.max_nodes(&NodeType::Server))
+        .is_some_and(|max_servers| level.servers.len() >= max_servers)
+        && level
+            .servers
+            .iter()
+            .all(|server| !server.hostname.is_empty());
+
+    let mut trace_ids = HashSet::new();
+
+    // The requests of each Client, then its hidden requests, in the order they are written.
+    let requests = level.clients.iter().flat_map(|client| {
+        client
+            .request_configs
+            .iter()
+            .chain(client.hidden_request_configs.iter())
+            .map(move |request_config| (client, request_config))
+    });
+
+    for (idx, (client, request_config)) in requests.enumerate() {
+        // Requests are checked once their placeholders are replaced by the generated data.
+        let request_config = match data
+            .as_ref()
+            .map(|data| data.render_request(request_config))
+        {
+            Some(Ok(rendered)) => Cow::Owned(rendered),
+            Some(Err(message)) => {
+                error(
+                    source.field_line("url", idx, 0),
+                    format!(
+                        "Client `{}` has a request that cannot be rendered: {message}",
+                        client.name
+                    ),
+                );
+                Cow::Borrowed(request_config)
+            }
+            None => Cow::Borrowed(request_config),
+        };
+
+        if !trace_ids.insert(request_config.trace_id()) {
+            error(
+                source.field_line("trace_id", idx, 0),
+                format!(
+                    "Client `{}` reuses trace id {}, which must be unique within the level.",
+                    client.name,
+                    request_config.trace_id()
+                ),
+            );
+        }
+
+        if !request_config.is_body_valid() {
+            error(
+                source.field_line("body", idx, 0),
+                format!(
+                    "Client `{}` has a request body that is not valid JSON.",
+                    client.name
+                ),
+            );
+        }
+
+        if !request_config.is_url_valid() {
+            error(
+                source.field_line("url", idx, 0),
+                format!("Client `{}` has a request without a URL.", client.name),
+            );
+        } else if database_hostnames.contains(request_config.url.as_str()) {
+            error(
+                source.field_line("url", idx, 0),
+                format!(
+                    "Client `{}` sends a request to Database `{}`, but Clients can only connect to Servers.",
+                    client.name, request_config.url
+                ),
+            );
+        } else if are_hostnames_fixed && !server_hostnames.contains(request_config.url.as_str()) {
+            error(
+                source.field_line("url", idx, 0),
+                format!(
+                    "Client `{}` sends a request to `{}`, which is not the hostname of any Server or Database of the level.",
+                    client.name, request_config.url
+                ),
+            );
+        }
+    }
+
+    let mut hostnames = HashSet::new();
+
+    for (idx, database) in level.databases.iter().enumerate() {
+        let line = source.field_line("initial_documents", idx, 0);
+
+        if database.hostname.is_empty() {
+            error(
+                line,
+                format!("Database `{}` has no hostname.", database.name),
+            );
+        } else if !hostnames.insert(&database.hostname) {
+            error(
+                line,
+                format!(
+                    "Database `{}` reuses hostname `{}`.",
+                    database.name, database.hostname
+                ),
+            );
+        }
+
+        let documents = database.documents(data.as_ref()).unwrap_or_else(|message| {
+            error(line, message);
+            vec![]
+        });
+
+        for (document_idx, document) in documents.iter().enumerate() {
+            if !document.get("id").is_some_and(Value::is_i64) {
+                error(
+                    line,
+                    format!(
+                        "Document {} of Database `{}` has no integer `id`.",
+                        document_idx + 1,
+                        database.name
+                    ),
+                );
+            }
+        }
+
+        let expectations = database
+            .render_expectations(data.as_ref())
+            .unwrap_or_else(|message| {
+                error(line, message);
+                vec![]
+            });
+
+        for expectation in expectations.iter() {
+            if let DatabaseExpectation::Contains(fields) = expectation {
+                if !fields.is_object() {
+                    error(
+                        line,
+                        format!(
+                            "Database `{}` expects to contain {fields}, which is not an object.",
+                            database.name
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    let node_types: HashMap<_, _> = level
+        .clients
+        .iter()
+        .map(|client| (&client.name, NodeType::Client))
+        .chain(
+            level
+                .databases
+                .iter()
+                .map(|database| (&database.name, NodeType::Database)),
+        )
+        .chain(
+            level
+                .servers
+                .iter()
+                .map(|server| (&server.name, NodeType::Server)),
+        )
+        .collect();
+
+    if let Some(constraints) = &level.constraints {
+        let line = source.field_line("constraints", 0, 0);
+
+        for name in constraints.locked_nodes.iter() {
+            if !node_types.contains_key(name) {
+                error(
+                    line,
+                    format!("Locked node `{name}` is not a node of the level."),
+                );
+            }
+        }
+
+        let counts = [
+            (NodeType::Client, level.clients.len()),
+            (NodeType::Server, level.servers.len()),
+            (NodeType::Database, level.databases.len()),
+        ];
+
+        for (node_type, count) in counts {
+            if constraints
+                .max_nodes(&node_type)
+                .is_some_and(|max| count > max)
+            {
+                error(
+                    line,
+                    format!("The level places more {node_type}s than its constraints allow."),
+                );
+            }
+        }
+    }
+
+    let chaos_line = source.field_line("chaos", 0, 0);
+
+    for (idx, event) in level.chaos.iter().enumerate() {
+        let line = source.field_line("fault", idx, 0).or(chaos_line);
+        let mut chaos_error = |message: String| {
+            error(line, format!("Chaos event {}: {message}", idx + 1));
+        };
+
+        if event
+            .until_ms
+            .is_some_and(|until_ms| until_ms <= event.at_ms)
+        {
+            chaos_error("The fault ends before it starts.".to_string());
+        }
+
+        let (names, allowed_types): (Vec<_>, &[NodeType]) = match &event.fault {
+            Fault::Crash(name) => (vec![name], &[NodeType::Server, NodeType::Database]),
+            Fault::ReadOnly(name) => (vec![name], &[NodeType::Database]),
+            Fault::DropMessages(a, b, rate) => {
+                if !(0.0..=1.0).contains(rate) {
+                    chaos_error(format!(
+                        "The chance of losing a message, {rate}, is not between 0 and 1."
+                    ));
+                }
+
+                (
+                    vec![a, b],
+                    &[NodeType::Client, NodeType::Server, NodeType::Database],
+                )
+            }
+        };
+
+        for name in names {
+            match node_types.get(name) {
+                Some(node_type) if !allowed_types.contains(node_type) => {
+                    chaos_error(format!("{node_type} `{name}` cannot have this fault."))
+                }
+                Some(_) => {}
+                None => chaos_error(format!("`{name}` is not a node of the level.")),
+            }
+        }
+    }
+
+    let servers_start = source.field_line("servers", 0, 0).unwrap_or_default();
+
+    for (idx, server) in level.servers.iter().enumerate() {
+        let line = source.field_line("name", idx, servers_start);
+
+        if server.max_concurrent_connections == Some(0) || server.workers == Some(0) {
+            error(
+                line,
+                format!(
+                    "Server `{}` has no capacity, so can never accept a connection.",
+                    server.name
+                ),
+            );
+        }
+
+        if server.max_backlog.is_some() && server.workers.is_none() {
+            error(
+                line,
+                format!(
+                    "Server `{}` has a `max_backlog` but no `workers`, so nothing ever waits in its backlog.",
+                    server.name
+                ),
+            );
+        }
+
+        if server
+            .cpu_cost
+            .is_some_and(|cpu_cost| !cpu_cost.is_finite() || cpu_cost < 0.0)
+        {
+            error(
+                line,
+                format!(
+                    "Server `{}` has a CPU cost that is negative or not a number.",
+                    server.name
+                ),
+            );
+        }
+
+        if !server.hostname.is_empty() && !hostnames.insert(&server.hostname) {
+            error(
+                line,
+                format!(
+                    "Server `{}` reuses hostname `{}`.",
+                    server.name, server.hostname
+                ),
+            );
+        }
+
+        for (endpoint_idx, endpoint) in server.endpoints.iter().enumerate() {
+            if !endpoint.is_valid() {
+                error(
+                    line,
+                    format!(
+                        "Endpoint {} of Server `{}` has an invalid path or handler.",
+                        endpoint_idx + 1,
+                        server.name
+                    ),
+                );
+            }
+        }
+
+        for other in server.connections.iter() {
+            match node_types.get(other) {
+                Some(node_type) if !NodeType::Server.is_valid_connection(node_type) => error(
+                    line,
+                    format!(
+                        "Server `{}` is connected to {node_type} `{other}`, which Servers cannot connect to.",
+                        server.name
+                    ),
+                ),
+                Some(_) => {}
+                None => error(
+                    line,
+                    format!(
+                        "Server `{}` is connected to `{other}`, which is not a node of the level.",
+                        server.name
+                    ),
+                ),
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Checks that the prerequisites of a level are all known level ids.
+pub fn validate_prerequisites(
+    path: &Path,
+    level: &Level,
+    source: Option<&str>,
+    level_ids: &HashSet<String>,
+) -> Vec<Diagnostic> {
+    let source = Source::new(source);
+
+    level
+        .prerequisites
+        .iter()
+        .filter(|prerequisite| !level_ids.contains(*prerequisite))
+        .map(|prerequisite| {
+            Diagnostic::new(
+                path,
+                source.field_line("prerequisites", 0, 0),
+                format!("Prerequisite `{prerequisite}` is not the id of any level."),
+            )
+        })
+        .collect()
+}
+
+/// Validates the manifest of every level directory and all of their levels, the same way the
+/// game loads them.
+pub fn validate_level_dirs(dirs: &[PathBuf]) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut level_ids = HashSet::new();
+    // The levels that parsed, by path, with their source.
+    let mut levels = vec![];
+
+    for dir in dirs {
+        let manifest_path = dir.join(MANIFEST_FILE);
+
+        let manifest_source = match fs::read_to_string(&manifest_path) {
+            Ok(source) => source,
+            Err(error) => {
+                diagnostics.push(Diagnostic::new(&manifest_path, None, error.to_string()));
+                continue;
+            }
+        };
+
+        let manifest = match parse_manifest(&manifest_path, &manifest_source) {
+            Ok(manifest) => manifest,
+            Err(diagnostic) => {
+                diagnostics.push(diagnostic);
+                continue;
+            }
+        };
+
+        let manifest_lines = Source::new(Some(&manifest_source));
+        let mut id_counts: HashMap<&str, usize> = HashMap::new();
+
+        for entry in manifest.chapters.iter().flat_map(|chapter| &chapter.levels) {
+            let id_count = id_counts.entry(entry.id.as_str()).or_default();
+
+            if !level_ids.insert(entry.id.clone()) {
+                diagnostics.push(Diagnostic::new(
+                    &manifest_path,
+                    manifest_lines.field_value_line("id", &entry.id, *id_count),
+                    format!("Level id `{}` is already used.", entry.id),
+                ));
+            }
+
+            *id_count += 1;
+
+            let level_path = dir.join(&entry.file);
+
+            let level_source = match fs::read_to_string(&level_path) {
+                Ok(source) => source,
+                Err(error) => {
+                    diagnostics.push(Diagnostic::new(
+                        &manifest_path,
+                        manifest_lines.field_value_line("file", &entry.file, 0),
+                        format!("Failed to read {}: {error}", level_path.display()),
+                    ));
+                    continue;
+                }
+            };
+
+            match parse_level(&level_path, &level_source) {
+                Ok(level) => {
+                    diagnostics.extend(validate_level(&level_path, &level, Some(&level_source)));
+                    levels.push((level_path, level, level_source));
+                }
+                Err(diagnostic) => diagnostics.push(diagnostic),
+            }
+        }
+    }
+
+    for (path, level, source) in levels.iter() {
+        diagnostics.extend(validate_prerequisites(
+            path,
+            level,
+            Some(source),
+            &level_ids,
+        ));
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LEVEL: &str = r#"Level(
+  name: "Users",
+  description: "",
+  clients: [
+    ClientConfig(
+      name: "user1",
+      x: 0.0,
+      y: 0.0,
+      request_configs: [
+        RequestConfig(
+          url: "users-api",
+          path: "/",
+          method: Post,
+          body: "{ name: 1 }",
+          trace_id: "F94A3B4B-B228-4B68-B554-FB044E5A9C3A",
+          expectations: [],
+          expectations_results: []
+        ),
+        RequestConfig(
+          url: "users-db",
+          path: "/",
+          method: Get,
+          body: "{}",
+          trace_id: "F94A3B4B-B228-4B68-B554-FB044E5A9C3A",
+          expectations: [],
+          expectations_results: []
+        )
+      ]
+    )
+  ],
+  databases: [
+    DatabaseConfig(
+      name: "users",
+      x: 0.0,
+      y: 0.0,
+      initial_documents: [{ "name": "Ada" }],
+      hostname: "users-db"
+    )
+  ],
+  servers: [
+    ServerConfig(
+      name: "api",
+      x: 0.0,
+      y: 0.0,
+      max_concurrent_connections: Some(0)
+    )
+  ],
+  prerequisites: ["hello"]
+)"#;
+
+    #[test]
+    fn should_report_line_and_column_of_syntax_errors() {
+        let path = Path::new("levels/1.level.ron");
+        let diagnostic = parse_level(path, "Level(\n  name: \"Users\",\n  clients: [,\n)")
+            .err()
+            .unwrap();
+
+        assert_eq!(Some(3), diagnostic.line);
+        assert!(diagnostic.to_string().starts_with("levels/1.level.ron:3:"));
+    }
+
+    #[test]
+    fn should_report_lines_of_invalid_level_contents() {
+        let path = Path::new("users.level.ron");
+        let level = parse_level(path, LEVEL).unwrap();
+
+        let diagnostics: Vec<_> = validate_level(path, &level, Some(LEVEL))
+            .into_iter()
+            .chain(validate_prerequisites(
+                path,
+                &level,
+                Some(LEVEL),
+                &HashSet::new(),
+            ))
+            .map(|diagnostic| (diagnostic.line, diagnostic.message))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (
+                    Some(14),
+                    "Client `user1` has a request body that is not valid JSON.".to_string()
+                ),
+                (
+                    Some(24),
+                    "Client `user1` reuses trace id f94a3b4b-b228-4b68-b554-fb044e5a9c3a, which must be unique within the level.".to_string()
+                ),
+                (
+                    Some(20),
+                    "Client `user1` sends a request to Database `users-db`, but Clients can only connect to Servers.".to_string()
+                ),
+                (
+                    Some(36),
+                    "Document 1 of Database `users` has no integer `id`.".to_string()
+                ),
+                (
+                    Some(42),
+                    "Server `api` has no capacity, so can never accept a connection.".to_string()
+                ),
+                (
+                    Some(48),
+                    "Prerequisite `hello` is not the id of any level.".to_string()
+                ),
+            ],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn should_report_invalid_pre_placed_servers() {
+        let source = r#"Level(
+  name: "Users",
+  description: "",
+  clients: [
+    ClientConfig(name: "user1", x: 0.0, y: 0.0, request_configs: [])
+  ],
+  databases: [
+    DatabaseConfig(name: "users", x: 0.0, y: 0.0, initial_documents: [], hostname: "users-db")
+  ],
+  servers: [
+    ServerConfig(
+      name: "api",
+      x: 0.0,
+      y: 0.0,
+      max_concurrent_connections: None,
+      cpu_cost: Some(-1.0),
+      workers: None,
+      max_backlog: Some(2),
+      hostname: "users-db",
+      endpoints: [
+        Endpoint(path: "/users", method: Get, handler: "const requestHandler = ", locked: true)
+      ],
+      connections: ["user1", "users", "cache"]
+    )
+  ]
+)"#;
+        let path = Path::new("users.level.ron");
+        let level = parse_level(path, source).unwrap();
+
+        let diagnostics: Vec<_> = validate_level(path, &level, Some(source))
+            .into_iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.message))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (
+                    Some(12),
+                    "Server `api` has a `max_backlog` but no `workers`, so nothing ever waits in its backlog.".to_string()
+                ),
+                (
+                    Some(12),
+                    "Server `api` has a CPU cost that is negative or not a number.".to_string()
+                ),
+                (
+                    Some(12),
+                    "Server `api` reuses hostname `users-db`.".to_string()
+                ),
+                (
+                    Some(12),
+                    "Endpoint 1 of Server `api` has an invalid path or handler.".to_string()
+                ),
+                (
+                    Some(12),
+                    "Server `api` is connected to `cache`, which is not a node of the level."
+                        .to_string()
+                ),
+            ],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn should_report_unknown_hostnames_when_players_cannot_add_servers() {
+        // The fields within the description and the comment are not counted when finding lines.
+        let source = r##"Level(
+  name: "Quotes",
+  description:
+r#"Send requests like:
+  url: "quotes.com",
+"#,
+  /* A comment about the first request,
+  url: "quotes.com" */
+  clients: [
+    ClientConfig(
+      name: "partner",
+      x: 0.0,
+      y: 0.0,
+      request_configs: [
+        RequestConfig(
+          url: "quotes.com",
+          path: "/quotes",
+          method: Get,
+          body: "{}",
+          trace_id: "7D3A9C2E-4B81-4F6A-9E05-1C8B2D7F3A46",
+          expectations: [],
+          expectations_results: []
+        ),
+        RequestConfig(
+          url: "quote.com",
+          path: "/quotes",
+          method: Get,
+          body: "{}",
+          trace_id: "E2B6F094-8C3D-4A17-B5E9-3D0A7C1F6B82",
+          expectations: [],
+          expectations_results: []
+        )
+      ]
+    )
+  ],
+  databases: [],
+  servers: [
+    ServerConfig(
+      name: "quotes-api",
+      x: 0.0,
+      y: 0.0,
+      max_concurrent_connections: None,
+      cpu_cost: None,
+      workers: None,
+      max_backlog: None,
+      hostname: "quotes.com"
+    )
+  ],
+  constraints: Some(Constraints(
+    max_servers: Some(1)
+  ))
+)"##;
+        let path = Path::new("quotes.level.ron");
+        let mut level = parse_level(path, source).unwrap();
+
+        let diagnostics = |level: &Level| -> Vec<_> {
+            validate_level(path, level, Some(source))
+                .into_iter()
+                .map(|diagnostic| (diagnostic.line, diagnostic.message))
+                .collect()
+        };
+
+        assert_eq!(
+            vec![(
+                Some(25),
+                "Client `partner` sends a request to `quote.com`, which is not the hostname of any Server or Database of the level.".to_string()
+            )],
+            diagnostics(&level)
+        );
+
+        // Once players can add a Server, it could take the hostname.
+        level.constraints = None;
+        assert!(diagnostics(&level).is_empty());
+    }
+}
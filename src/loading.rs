@@ -1,38 +1,205 @@
+use std::{
+    collections::HashSet,
+    env, iter,
+    path::{Path, PathBuf},
+};
+
 use bevy::{asset::LoadState, prelude::*};
 
-use crate::{game_state::AppState, Handles};
+use crate::{
+    game_state::AppState,
+    level::{Level, LevelInfo, LevelManifest},
+    level_validation::{
+        parse_level, parse_manifest, validate_level, validate_prerequisites, Diagnostic,
+        MANIFEST_FILE,
+    },
+    Handles,
+};
 
 pub struct LoadingPlugin;
 
-pub const NUM_LEVELS: u32 = 4;
+/// Directories of extra level packs, set with the `--level-pack <dir>` command line argument.
+/// Their levels are listed after the built-in levels.
+#[derive(Resource, Default, Debug)]
+pub struct LevelPacks(pub Vec<PathBuf>);
+
+/// The problems found with the manifests and levels while loading. Levels with problems are
+/// left out, and the problems are shown before continuing to the main menu.
+#[derive(Resource, Default, Debug)]
+pub struct LevelLoadErrors(pub Vec<Diagnostic>);
+
+pub const LEVELS_DIR: &str = "levels";
 
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Handles>();
+        app.init_resource::<LevelPacks>();
+        app.init_resource::<LevelLoadErrors>();
         app.add_system(loading_setup.in_schedule(OnEnter(AppState::Loading)));
         app.add_system(loading_update.in_set(OnUpdate(AppState::Loading)));
     }
 }
 
-fn loading_setup(mut handles: ResMut<Handles>, asset_server: Res<AssetServer>) {
-    for i in 1..=NUM_LEVELS {
+fn loading_setup(
+    mut handles: ResMut<Handles>,
+    asset_server: Res<AssetServer>,
+    level_packs: Res<LevelPacks>,
+) {
+    // Asset paths are relative to the assets directory, so level packs given relative to the
+    // working directory are made absolute.
+    let level_packs = level_packs.0.iter().map(|dir| {
+        env::current_dir()
+            .map(|working_dir| working_dir.join(dir))
+            .unwrap_or_else(|_| dir.clone())
+    });
+
+    for dir in iter::once(PathBuf::from(LEVELS_DIR)).chain(level_packs) {
         handles
-            .levels
-            .push(asset_server.load(format!("levels/{i}.level.ron").as_str()));
+            .manifests
+            .push((asset_server.load(dir.join(MANIFEST_FILE)), dir));
     }
 }
 
+/// The directory assets are loaded from.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn assets_dir() -> PathBuf {
+    bevy::asset::FileAssetIo::get_base_path().join("assets")
+}
+
+// Reads the source of an asset, to find where the problems in it are. Assets are fetched over the
+// network on the web, so are not read again there.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_asset_source(path: &Path) -> Option<String> {
+    std::fs::read_to_string(assets_dir().join(path)).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_asset_source(_path: &Path) -> Option<String> {
+    None
+}
+
+fn load_failure(path: &Path, parse: impl FnOnce(&str) -> Option<Diagnostic>) -> Diagnostic {
+    read_asset_source(path)
+        .and_then(|source| parse(&source))
+        .unwrap_or_else(|| Diagnostic::new(path, None, "Failed to load.".to_string()))
+}
+
+#[allow(clippy::too_many_arguments)]
 fn loading_update(
-    handles: Res<Handles>,
+    mut handles: ResMut<Handles>,
     asset_server: Res<AssetServer>,
+    manifests: Res<Assets<LevelManifest>>,
+    level_assets: Res<Assets<Level>>,
+    mut level_load_errors: ResMut<LevelLoadErrors>,
     mut next_state: ResMut<NextState<AppState>>,
+    mut manifests_read: Local<bool>,
+    mut levels_read: Local<bool>,
 ) {
-    if !matches!(
-        asset_server.get_group_load_state(handles.levels.iter().cloned().map(|h| h.id())),
-        LoadState::Loaded
-    ) {
+    if *levels_read {
         return;
     }
 
-    next_state.set(AppState::MainMenu);
+    if !*manifests_read {
+        if handles.manifests.iter().any(|(handle, _)| {
+            matches!(
+                asset_server.get_load_state(handle),
+                LoadState::NotLoaded | LoadState::Loading
+            )
+        }) {
+            return;
+        }
+
+        let mut levels: Vec<LevelInfo> = vec![];
+
+        for (handle, dir) in handles.manifests.iter() {
+            let manifest_path = dir.join(MANIFEST_FILE);
+
+            let Some(manifest) = manifests.get(handle) else {
+                level_load_errors
+                    .0
+                    .push(load_failure(&manifest_path, |source| {
+                        parse_manifest(&manifest_path, source).err()
+                    }));
+                continue;
+            };
+
+            for chapter in manifest.chapters.iter() {
+                for entry in chapter.levels.iter() {
+                    if levels.iter().any(|level| level.id == entry.id) {
+                        level_load_errors.0.push(Diagnostic::new(
+                            &manifest_path,
+                            None,
+                            format!("Level id `{}` is already used.", entry.id),
+                        ));
+                        continue;
+                    }
+
+                    let path = dir.join(&entry.file);
+
+                    levels.push(LevelInfo {
+                        id: entry.id.clone(),
+                        chapter: chapter.name.clone(),
+                        handle: asset_server.load(path.clone()),
+                        path,
+                    });
+                }
+            }
+        }
+
+        handles.levels = levels;
+        *manifests_read = true;
+    }
+
+    // Wait for every level to either load or fail, rather than for all of them to load.
+    if handles.levels.iter().any(|level| {
+        matches!(
+            asset_server.get_load_state(&level.handle),
+            LoadState::NotLoaded | LoadState::Loading
+        )
+    }) {
+        return;
+    }
+
+    let level_ids: HashSet<_> = handles
+        .levels
+        .iter()
+        .map(|level| level.id.clone())
+        .collect();
+
+    handles.levels.retain(|level_info| {
+        let path = &level_info.path;
+
+        let Some(level) = level_assets.get(&level_info.handle) else {
+            level_load_errors
+                .0
+                .push(load_failure(path, |source| parse_level(path, source).err()));
+            return false;
+        };
+
+        let source = read_asset_source(path);
+        let diagnostics: Vec<_> = validate_level(path, level, source.as_deref())
+            .into_iter()
+            .chain(validate_prerequisites(
+                path,
+                level,
+                source.as_deref(),
+                &level_ids,
+            ))
+            .collect();
+
+        let is_valid = diagnostics.is_empty();
+        level_load_errors.0.extend(diagnostics);
+        is_valid
+    });
+
+    *levels_read = true;
+
+    for diagnostic in level_load_errors.0.iter() {
+        error!("{diagnostic}");
+    }
+
+    // Otherwise the problems are shown, until the player continues to the main menu.
+    if level_load_errors.0.is_empty() {
+        next_state.set(AppState::MainMenu);
+    }
 }
@@ -1,29 +1,50 @@
+use std::{iter, path::PathBuf, process};
+
+use args::Args;
 use bevy::prelude::*;
 use bevy_common_assets::ron::RonAssetPlugin;
 use bevy_egui::EguiPlugin;
 use bevy_mod_picking::{prelude::RaycastPickCamera, DefaultPickingPlugins};
 use bevy_prototype_lyon::prelude::*;
+use chaos::ChaosPlugin;
+use console::ConsolePlugin;
 use events::AddComponentEvent;
 use game_state::{AppState, GameMode};
 use game_ui::GameUiPlugin;
 use grid::GridPlugin;
-use level::{Level, LevelState};
-use loading::LoadingPlugin;
-use message::{MessageArrivedEvent, MessagePlugin, SendMessageEvent};
-
+use level::{Level, LevelInfo, LevelManifest, LevelState};
+use level_editor::LevelEditorPlugin;
+use loading::{LevelPacks, LoadingPlugin};
+use message::{MessageArrivedEvent, MessageFailedEvent, MessagePlugin, SendMessageEvent};
+use metrics::MetricsPlugin;
+use progress::ProgressPlugin;
+use score::ScorePlugin;
 use simulation::SimulationPlugin;
+use trace::TracePlugin;
 
+mod args;
+mod chaos;
 mod color;
+mod console;
+mod constraints;
+mod cost;
 mod events;
 mod game_state;
 mod game_ui;
 mod grid;
 mod layer;
 mod level;
+mod level_data;
+mod level_editor;
+mod level_validation;
 mod loading;
 mod message;
+mod metrics;
 mod node;
+mod progress;
+mod score;
 mod simulation;
+mod trace;
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
 pub struct MainMenuSet;
@@ -42,6 +63,25 @@ fn main() {
 
     app.init_resource::<LevelState>();
 
+    match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => {
+            #[cfg(not(target_arch = "wasm32"))]
+            if args.validate_levels {
+                validate_levels(&args.level_packs);
+            }
+
+            if let Some(trace_export_config) = args.trace_export {
+                app.insert_resource(trace_export_config);
+            }
+
+            app.insert_resource(LevelPacks(args.level_packs));
+        }
+        Err(error) => {
+            eprintln!("{error}");
+            process::exit(2);
+        }
+    }
+
     let default = DefaultPlugins
         .set(WindowPlugin {
             primary_window: Some(Window {
@@ -56,32 +96,41 @@ fn main() {
     app.add_event::<AddComponentEvent>();
     app.add_event::<SendMessageEvent>();
     app.add_event::<MessageArrivedEvent>();
+    app.add_event::<MessageFailedEvent>();
 
     app.add_state::<AppState>();
     app.add_state::<GameMode>();
 
     app.configure_set(MainMenuSet.run_if(in_state(AppState::MainMenu)));
-    app.configure_set(EditSet.run_if(in_state(AppState::Edit)));
-
     app.configure_set(
-        GridSet.run_if(
-            in_state(AppState::Edit).or_else(
-                in_state(AppState::Validate).or_else(
-                    in_state(AppState::Simulate).or_else(in_state(AppState::SimulateFinish)),
-                ),
+        EditSet.run_if(in_state(AppState::Edit).or_else(in_state(AppState::LevelEditor))),
+    );
+
+    app.configure_set(GridSet.run_if(in_state(AppState::Edit).or_else(
+        in_state(AppState::Validate).or_else(
+            in_state(AppState::Simulate).or_else(
+                in_state(AppState::SimulateFinish).or_else(in_state(AppState::LevelEditor)),
             ),
         ),
-    );
+    )));
 
     app.add_plugins(default)
         .add_plugin(RonAssetPlugin::<Level>::new(&["level.ron"]))
+        .add_plugin(RonAssetPlugin::<LevelManifest>::new(&["manifest.ron"]))
         .add_plugin(ShapePlugin)
         .add_plugin(EguiPlugin)
         .add_plugin(LoadingPlugin)
+        .add_plugin(ConsolePlugin)
         .add_plugin(GameUiPlugin)
         .add_plugin(GridPlugin)
         .add_plugin(MessagePlugin)
         .add_plugin(SimulationPlugin)
+        .add_plugin(ChaosPlugin)
+        .add_plugin(TracePlugin)
+        .add_plugin(MetricsPlugin)
+        .add_plugin(ScorePlugin)
+        .add_plugin(ProgressPlugin)
+        .add_plugin(LevelEditorPlugin)
         .add_plugins(DefaultPickingPlugins);
 
     app.add_startup_system(setup);
@@ -89,11 +138,36 @@ fn main() {
     app.run();
 }
 
+/// Checks the built-in levels and the level packs, printing every problem found, then exits with
+/// a failure status if there were any.
+#[cfg(not(target_arch = "wasm32"))]
+fn validate_levels(level_packs: &[PathBuf]) -> ! {
+    let dirs: Vec<_> = iter::once(loading::assets_dir().join(loading::LEVELS_DIR))
+        .chain(level_packs.iter().cloned())
+        .collect();
+
+    let diagnostics = level_validation::validate_level_dirs(&dirs);
+
+    if diagnostics.is_empty() {
+        println!("All levels are valid.");
+        process::exit(0);
+    }
+
+    for diagnostic in diagnostics.iter() {
+        eprintln!("error: {diagnostic}");
+    }
+
+    eprintln!("Found {} problem(s) in the level files.", diagnostics.len());
+    process::exit(1);
+}
+
 fn setup(mut commands: Commands) {
     commands.spawn((Camera2dBundle::default(), RaycastPickCamera::default()));
 }
 
 #[derive(Resource, Default)]
 pub struct Handles {
-    levels: Vec<Handle<Level>>,
+    // Every manifest, with the directory its level files are relative to.
+    manifests: Vec<(Handle<LevelManifest>, PathBuf)>,
+    levels: Vec<LevelInfo>,
 }
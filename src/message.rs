@@ -1,24 +1,34 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
 
 use bevy::{
     prelude::*,
     sprite::{ColorMaterial, MaterialMesh2dBundle},
 };
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
 
-use crate::node::{
-    client::{Client, HttpMethod, RequestConfig},
-    database::Database,
-    server::Server,
-    SystemNodeTrait,
+use crate::{
+    chaos::Faults,
+    node::{
+        client::{Client, HttpMethod, RequestConfig},
+        database::Database,
+        server::Server,
+        upstream::CallOptions,
+        SystemNodeTrait,
+    },
+    simulation::SimulationRng,
 };
 
 pub struct MessagePlugin;
 
+/// Systems that handle messages arriving at their recipient.
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
-struct MessageArrivedEventSet;
+pub struct MessageArrivedEventSet;
 
 impl Plugin for MessagePlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
@@ -47,7 +57,40 @@ pub struct MessageComponent {
     pub sender: Entity,
     pub recipient: Entity,
     pub message: Message,
+    pub span: SpanContext,
+    // Whether the message will be lost on its way to the recipient.
+    pub dropped: bool,
+    // How long the message still waits at the recipient before being received.
+    pub delay: Duration,
+}
+
+/// Identifies the request/response exchange a message belongs to, and where it sits in the trace
+/// of the Client request that caused it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpanContext {
     pub trace_id: Uuid,
+    pub span_id: Uuid,
+    pub parent_span_id: Option<Uuid>,
+}
+
+impl SpanContext {
+    /// The span of a request sent by a Client, which shares its id with the trace.
+    pub fn root(trace_id: Uuid) -> Self {
+        Self {
+            trace_id,
+            span_id: trace_id,
+            parent_span_id: None,
+        }
+    }
+
+    /// A new span for a call made while handling this span.
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id,
+            span_id: Uuid::new_v4(),
+            parent_span_id: Some(self.span_id),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -56,6 +99,20 @@ pub enum Message {
     Response(Response),
     DatabaseCall(DatabaseCall),
     DatabaseAnswer(Value),
+    // Sent instead of an answer when a Database call fails.
+    DatabaseError(String),
+    // A probe of the health endpoint of a node, answered with a Response.
+    HealthCheck,
+}
+
+impl Message {
+    pub fn is_error(&self) -> bool {
+        match self {
+            Message::Response(response) => response.status >= 500,
+            Message::DatabaseError(_) => true,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
@@ -64,13 +121,20 @@ pub struct Request {
     pub path: String,
     pub method: HttpMethod,
     pub body: Value,
-    pub params: HashMap<String, String>,
+    pub params: HashMap<String, Value>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    // How the calling Server sends the request, which is not passed on to the upstream.
+    #[serde(default, skip_serializing)]
+    pub options: Option<CallOptions>,
 }
 
 impl From<&mut RequestConfig> for Request {
     fn from(value: &mut RequestConfig) -> Self {
         let body = match value.method {
-            HttpMethod::Get | HttpMethod::Delete => Value::Null,
+            HttpMethod::Get | HttpMethod::Delete | HttpMethod::Head | HttpMethod::Options => {
+                Value::Null
+            }
             HttpMethod::Post | HttpMethod::Put => serde_json::from_str(&value.body).unwrap(), // TODO: handle if this fails
         };
 
@@ -80,6 +144,8 @@ impl From<&mut RequestConfig> for Request {
             method: value.method,
             body,
             params: HashMap::new(),
+            headers: value.headers.iter().cloned().collect(),
+            options: None,
         }
     }
 }
@@ -88,24 +154,56 @@ impl From<&mut RequestConfig> for Request {
 pub struct Response {
     pub status: u16,
     pub data: Value,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
 }
 
 impl Response {
+    pub fn healthy() -> Self {
+        Self {
+            status: 200,
+            data: Value::String("OK".to_string()),
+            ..Default::default()
+        }
+    }
+
     pub fn not_found() -> Self {
         Self {
             status: 404,
             data: Value::String("Not found.".to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn method_not_allowed(allowed_methods: &[HttpMethod]) -> Self {
+        Self {
+            status: 405,
+            data: Value::String("Method not allowed.".to_string()),
+            ..Default::default()
+        }
+        .with_allow_header(allowed_methods)
+    }
+
+    pub fn no_content() -> Self {
+        Self {
+            status: 204,
+            ..Default::default()
         }
     }
 
     pub fn internal_server_error(data: Value) -> Self {
-        Self { status: 500, data }
+        Self {
+            status: 500,
+            data,
+            ..Default::default()
+        }
     }
 
     pub fn bad_request() -> Self {
         Self {
             status: 400,
             data: Value::String("Bad request.".to_string()),
+            ..Default::default()
         }
     }
 
@@ -113,8 +211,53 @@ impl Response {
         Self {
             status: 503,
             data: Value::String("Service Unavailable.".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Rejects a request over a rate limit, asking the sender to wait `retry_after` before
+    /// sending another. `Retry-After` is in whole seconds, so is rounded up.
+    pub fn too_many_requests(retry_after: Duration) -> Self {
+        let retry_after_secs = retry_after.as_millis().div_ceil(1000).max(1);
+
+        let mut response = Self {
+            status: 429,
+            data: Value::String("Too many requests.".to_string()),
+            ..Default::default()
+        };
+
+        response
+            .headers
+            .insert("Retry-After".to_string(), retry_after_secs.to_string());
+
+        response
+    }
+
+    pub fn circuit_open() -> Self {
+        Self {
+            status: 503,
+            data: Value::String("Circuit breaker open.".to_string()),
+            ..Default::default()
         }
     }
+
+    pub fn gateway_timeout() -> Self {
+        Self {
+            status: 504,
+            data: Value::String("Gateway Timeout.".to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_allow_header(mut self, allowed_methods: &[HttpMethod]) -> Self {
+        let allow: Vec<_> = allowed_methods
+            .iter()
+            .map(|m| m.to_string().to_ascii_uppercase())
+            .collect();
+
+        self.headers.insert("Allow".to_string(), allow.join(", "));
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -136,7 +279,7 @@ pub struct SendMessageEvent {
     pub sender: Entity,
     pub recipients: Vec<Entity>,
     pub message: Message,
-    pub trace_id: Uuid,
+    pub span: SpanContext,
 }
 
 fn handle_send_message_event(
@@ -145,13 +288,25 @@ fn handle_send_message_event(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     transforms: Query<&Transform>,
+    mut faults: ResMut<Faults>,
+    mut rng: ResMut<SimulationRng>,
 ) {
     for event in send_message_events.iter() {
         for recipient in event.recipients.iter() {
+            let drop_rate = faults.drop_rate(event.sender, *recipient);
+            let dropped = (drop_rate > 0.0 && rng.0.gen::<f32>() < drop_rate)
+                || (faults.drops_remaining(*recipient) > 0 && faults.take_drop(*recipient));
+
+            let color = if dropped || event.message.is_error() {
+                Color::RED
+            } else {
+                Color::YELLOW
+            };
+
             commands.spawn((
                 MaterialMesh2dBundle {
                     mesh: meshes.add(shape::Circle::new(15.).into()).into(),
-                    material: materials.add(ColorMaterial::from(Color::YELLOW)),
+                    material: materials.add(ColorMaterial::from(color)),
                     transform: Transform::from_translation(
                         transforms.get(event.sender).unwrap().translation,
                     ),
@@ -161,27 +316,41 @@ fn handle_send_message_event(
                     sender: event.sender,
                     recipient: *recipient,
                     message: event.message.clone(),
-                    trace_id: event.trace_id,
+                    span: event.span,
+                    dropped,
+                    delay: faults.latency(*recipient),
                 },
             ));
         }
     }
 }
 
-fn move_messages(
+pub fn move_messages(
     mut commands: Commands,
-    mut messages: Query<(Entity, &MessageComponent, &mut Transform)>,
+    mut messages: Query<(Entity, &mut MessageComponent, &mut Transform)>,
     transforms: Query<&Transform, Without<MessageComponent>>,
     time: Res<Time>,
+    faults: Res<Faults>,
     mut events: EventWriter<MessageArrivedEvent>,
+    mut failed_events: EventWriter<MessageFailedEvent>,
 ) {
-    for (message_entity, message, mut message_transform) in messages.iter_mut() {
+    for (message_entity, mut message, mut message_transform) in messages.iter_mut() {
         let current_pos = message_transform.translation;
         let destination = transforms.get(message.recipient).unwrap().translation;
         let delta_to_destination = destination - current_pos;
 
         if delta_to_destination.length_squared() < 0.25 {
-            events.send(MessageArrivedEvent(message.clone()));
+            if !message.delay.is_zero() {
+                message.delay = message.delay.saturating_sub(time.delta());
+                continue;
+            }
+
+            if message.dropped || faults.is_down(message.recipient) {
+                failed_events.send(MessageFailedEvent(message.clone()));
+            } else {
+                events.send(MessageArrivedEvent(message.clone()));
+            }
+
             commands.entity(message_entity).despawn_recursive();
         } else {
             let movement_delta = 100.0 * time.delta_seconds() * delta_to_destination.normalize();
@@ -192,6 +361,10 @@ fn move_messages(
 
 pub struct MessageArrivedEvent(pub MessageComponent);
 
+/// A message that reached its recipient, but could not be delivered because it was lost on the
+/// way or the recipient is down.
+pub struct MessageFailedEvent(pub MessageComponent);
+
 fn handle_message_for<T: SystemNodeTrait + Component>(
     mut events: EventReader<MessageArrivedEvent>,
     mut nodes: Query<&mut T>,
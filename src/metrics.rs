@@ -0,0 +1,234 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    game_state::AppState,
+    message::{Message, SendMessageEvent},
+    node::server::{server_system, Server},
+    simulation::SimulationClock,
+    trace::SpanRecord,
+};
+
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Metrics>();
+        app.add_system(clear_metrics.in_schedule(OnEnter(AppState::Simulate)));
+        // Requests from a previous simulation no longer count towards the cost of the system.
+        app.add_system(clear_metrics.in_schedule(OnEnter(AppState::Edit)));
+        app.add_system(count_sent_messages.run_if(on_event::<SendMessageEvent>()));
+        app.add_system(
+            sample_server_metrics
+                .after(server_system)
+                .in_set(OnUpdate(AppState::Simulate)),
+        );
+    }
+}
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The load on a server at a point in simulation time.
+#[derive(Clone, Copy, Debug)]
+pub struct ServerSample {
+    pub time: Duration,
+    // Steps waiting for a free worker.
+    pub queue_depth: usize,
+    // Requests being handled, including those waiting on upstream calls.
+    pub concurrency: usize,
+}
+
+/// Periodic samples of every server's load during the current simulation.
+#[derive(Resource, Default)]
+pub struct Metrics {
+    samples: Vec<(Entity, ServerSample)>,
+    next_sample: Duration,
+    messages_sent: usize,
+    requests_sent: usize,
+}
+
+impl Metrics {
+    pub fn messages_sent(&self) -> usize {
+        self.messages_sent
+    }
+
+    /// The number of requests and Database calls sent, excluding their responses.
+    pub fn requests_sent(&self) -> usize {
+        self.requests_sent
+    }
+
+    pub fn server_samples(&self, server: Entity) -> impl Iterator<Item = &ServerSample> {
+        self.samples
+            .iter()
+            .filter(move |(entity, _)| *entity == server)
+            .map(|(_, sample)| sample)
+    }
+}
+
+fn clear_metrics(mut metrics: ResMut<Metrics>) {
+    metrics.samples.clear();
+    metrics.next_sample = Duration::ZERO;
+    metrics.messages_sent = 0;
+    metrics.requests_sent = 0;
+}
+
+fn count_sent_messages(mut metrics: ResMut<Metrics>, mut events: EventReader<SendMessageEvent>) {
+    for event in events.iter() {
+        metrics.messages_sent += event.recipients.len();
+
+        if let Message::Request(_) | Message::DatabaseCall(_) = event.message {
+            metrics.requests_sent += event.recipients.len();
+        }
+    }
+}
+
+fn sample_server_metrics(
+    mut metrics: ResMut<Metrics>,
+    servers: Query<(Entity, &Server)>,
+    simulation_clock: Res<SimulationClock>,
+) {
+    let now = simulation_clock.elapsed();
+
+    if now < metrics.next_sample {
+        return;
+    }
+
+    for (entity, server) in servers.iter() {
+        metrics.samples.push((
+            entity,
+            ServerSample {
+                time: now,
+                queue_depth: server.num_queued_steps(),
+                concurrency: server.num_connections(),
+            },
+        ));
+    }
+
+    metrics.next_sample = now + SAMPLE_INTERVAL;
+}
+
+/// Request counts, statuses and latencies of a group of completed spans.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestStats {
+    pub count: usize,
+    // Responses by status class, from 1xx to 5xx.
+    pub status_classes: [usize; 5],
+    // Sorted in ascending order.
+    latencies: Vec<Duration>,
+}
+
+impl RequestStats {
+    pub fn from_spans<'a>(spans: impl Iterator<Item = &'a SpanRecord>) -> Self {
+        let mut stats = Self::default();
+
+        for span in spans {
+            let Some(end) = span.end else {
+                continue;
+            };
+
+            stats.count += 1;
+            stats.latencies.push(end.saturating_sub(span.start));
+
+            if let Some(class @ 1..=5) = span.status.map(|status| status / 100) {
+                stats.status_classes[usize::from(class) - 1] += 1;
+            }
+        }
+
+        stats.latencies.sort();
+        stats
+    }
+
+    /// The fraction of requests that received a 4xx or 5xx response.
+    pub fn error_rate(&self) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        (self.status_classes[3] + self.status_classes[4]) as f32 / self.count as f32
+    }
+
+    /// Completed requests per second of simulation time, over the `elapsed` time so far.
+    pub fn throughput(&self, elapsed: Duration) -> f32 {
+        if elapsed.is_zero() {
+            return 0.0;
+        }
+
+        self.count as f32 / elapsed.as_secs_f32()
+    }
+
+    /// The latency at or below which `percentile` percent of requests completed, by nearest rank.
+    pub fn latency_percentile(&self, percentile: f32) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+
+        let rank = (percentile * self.latencies.len() as f32 / 100.0).ceil() as usize;
+
+        self.latencies.get(rank.max(1) - 1).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use uuid::Uuid;
+
+    use crate::{message::SpanContext, trace::SpanOperation};
+
+    use super::*;
+
+    fn span(latency_ms: u64, status: Option<u16>) -> SpanRecord {
+        SpanRecord {
+            context: SpanContext::root(Uuid::new_v4()),
+            caller: Entity::from_raw(0),
+            callee: Entity::from_raw(1),
+            operation: SpanOperation::Database {
+                name: "users".to_string(),
+                call: "findAll",
+            },
+            endpoint: None,
+            start: Duration::from_millis(1000),
+            end: Some(Duration::from_millis(1000 + latency_ms)),
+            status,
+        }
+    }
+
+    #[test]
+    fn should_count_status_classes_and_skip_spans_in_flight() {
+        let mut in_flight = span(0, None);
+        in_flight.end = None;
+
+        let spans = [
+            span(10, Some(200)),
+            span(10, Some(201)),
+            span(10, Some(404)),
+            span(10, Some(503)),
+            in_flight,
+        ];
+
+        let stats = RequestStats::from_spans(spans.iter());
+
+        assert_eq!(4, stats.count);
+        assert_eq!([0, 2, 0, 1, 1], stats.status_classes);
+        assert_eq!(0.5, stats.error_rate());
+        assert_eq!(2.0, stats.throughput(Duration::from_secs(2)));
+        assert_eq!(0.0, stats.throughput(Duration::ZERO));
+    }
+
+    #[test]
+    fn should_return_nearest_rank_latency_percentiles() {
+        let spans: Vec<_> = (1..=100).rev().map(|ms| span(ms, Some(200))).collect();
+
+        let stats = RequestStats::from_spans(spans.iter());
+
+        assert_eq!(
+            Some(Duration::from_millis(50)),
+            stats.latency_percentile(50.0)
+        );
+        assert_eq!(
+            Some(Duration::from_millis(99)),
+            stats.latency_percentile(99.0)
+        );
+        assert_eq!(None, RequestStats::default().latency_percentile(50.0));
+    }
+}
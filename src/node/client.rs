@@ -6,16 +6,21 @@ use serde_json::Value;
 use strum::EnumIter;
 use uuid::Uuid;
 
-use crate::message::{Message, MessageComponent, Request, Response, SendMessageEvent};
+use crate::message::{Message, MessageComponent, Request, Response, SendMessageEvent, SpanContext};
 
 use super::{HostnameConnections, SystemNodeTrait};
 
 #[derive(Component, Clone, Debug, Default)]
 pub struct Client {
     pub request_configs: Vec<RequestConfig>,
+    // Requests sent after the others, whose expectations are not shown to the player.
+    pub hidden_request_configs: Vec<RequestConfig>,
     pub state: ClientState,
     curr_request_idx: usize,
     can_be_edited: bool,
+    // What every response of the Client, together, must be once it has finished.
+    expectations: Vec<ClientExpectation>,
+    pub expectations_results: Vec<(bool, String)>,
 }
 
 impl Client {
@@ -37,20 +42,60 @@ impl Client {
         self
     }
 
+    pub fn hidden_request_configs(mut self, hidden_request_configs: Vec<RequestConfig>) -> Self {
+        self.hidden_request_configs = hidden_request_configs;
+        self
+    }
+
+    pub fn expectations(mut self, expectations: Vec<ClientExpectation>) -> Self {
+        self.expectations = expectations;
+        self
+    }
+
+    pub fn get_expectations(&self) -> &[ClientExpectation] {
+        &self.expectations
+    }
+
+    pub fn expectations_mut(&mut self) -> &mut Vec<ClientExpectation> {
+        &mut self.expectations
+    }
+
+    // Every request, in the order they are sent.
+    fn all_request_configs_mut(&mut self) -> impl Iterator<Item = &mut RequestConfig> {
+        self.request_configs
+            .iter_mut()
+            .chain(self.hidden_request_configs.iter_mut())
+    }
+
     fn reset(&mut self) {
         self.state = ClientState::SimulationNotStarted;
         self.curr_request_idx = 0;
+        self.expectations_results.clear();
 
-        for r in self.request_configs.iter_mut() {
+        for r in self.all_request_configs_mut() {
             r.reset();
         }
     }
 
     pub fn verify(&mut self) -> bool {
-        self.request_configs
-            .iter_mut()
-            .map(|request_config| request_config.verify())
-            .all(|b| b)
+        let passed = self
+            .all_request_configs_mut()
+            .all(|request_config| request_config.verify());
+
+        let responses: Vec<_> = self
+            .request_configs
+            .iter()
+            .chain(self.hidden_request_configs.iter())
+            .filter_map(|request_config| request_config.response.as_ref())
+            .collect();
+
+        self.expectations_results = self
+            .expectations
+            .iter()
+            .map(|expectation| expectation.verify(&responses))
+            .collect();
+
+        passed && self.expectations_results.iter().all(|(passed, _)| *passed)
     }
 
     pub fn is_valid(&self) -> bool {
@@ -65,34 +110,26 @@ impl SystemNodeTrait for Client {
 
     fn handle_message(&mut self, message: MessageComponent) {
         if let ClientState::Waiting(trace_id) = self.state {
-            println!("HANDLING MESSAGE FOR CLIENT:");
-            println!("{:?}", message);
-
-            if trace_id == message.trace_id {
+            if trace_id == message.span.trace_id {
                 if let Message::Response(response) = message.message {
-                    println!("RECEIVED CORRECT RESPONSE");
-
                     let request_config = self
-                        .request_configs
-                        .iter_mut()
-                        .find(|r| r.trace_id == message.trace_id)
+                        .all_request_configs_mut()
+                        .find(|r| r.trace_id == message.span.trace_id)
                         .unwrap();
 
                     request_config.response = Some(response);
                     self.curr_request_idx += 1;
 
-                    if self.curr_request_idx >= self.request_configs.len() {
-                        println!("CLIENT SENT ALL REQUESTS");
+                    if self.curr_request_idx
+                        >= self.request_configs.len() + self.hidden_request_configs.len()
+                    {
                         self.state = ClientState::Finished;
                     } else {
                         self.state = ClientState::SendNextRequest;
                     }
-                    return;
                 }
             }
         }
-
-        println!("RECEIVED UNEXPECTED RESPONSE");
     }
 
     fn can_be_edited(&self) -> bool {
@@ -104,13 +141,17 @@ impl SystemNodeTrait for Client {
     }
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RequestConfig {
     pub url: String,
     pub path: String,
     pub method: HttpMethod,
     pub body: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<(String, String)>,
     trace_id: Uuid,
+    // Only known during a simulation, so never saved.
+    #[serde(default, skip_serializing)]
     pub response: Option<Response>,
     expectations: Vec<ResponseExpectation>,
     pub expectations_results: Vec<(bool, String)>,
@@ -123,6 +164,7 @@ impl Default for RequestConfig {
             path: "/".to_string(),
             url: "".to_string(),
             method: HttpMethod::default(),
+            headers: Default::default(),
             trace_id: Uuid::new_v4(),
             response: None,
             expectations: vec![],
@@ -158,6 +200,18 @@ impl RequestConfig {
         }
     }
 
+    pub fn trace_id(&self) -> Uuid {
+        self.trace_id
+    }
+
+    pub fn expectations(&self) -> &[ResponseExpectation] {
+        &self.expectations
+    }
+
+    pub fn expectations_mut(&mut self) -> &mut Vec<ResponseExpectation> {
+        &mut self.expectations
+    }
+
     pub fn is_url_valid(&self) -> bool {
         !self.url.is_empty()
     }
@@ -176,11 +230,12 @@ impl RequestConfig {
     }
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum ResponseExpectation {
     Status(u16),
     ExactBody(Value),
     ExactSet(Vec<Value>),
+    Header(String, String),
 }
 impl ResponseExpectation {
     fn verify(&self, response: &Response) -> (bool, String) {
@@ -202,13 +257,59 @@ impl ResponseExpectation {
                         msg.push_str(&format!(", received {:?}", actual_elems));
                     }
 
-                    println!("ExactSet expectation:");
-                    println!("{:?}", msg);
-
                     (passed, msg)
                 }
                 _ => (false, String::default()),
             },
+            ResponseExpectation::Header(name, expected) => get_expectation_result(
+                &format!("header {name}"),
+                expected,
+                response.headers.get(name).unwrap_or(&String::new()),
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ClientExpectation {
+    // At least one response was `429 Too Many Requests`.
+    Throttled,
+    // No response was `429 Too Many Requests`.
+    NotThrottled,
+}
+
+impl Display for ClientExpectation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientExpectation::Throttled => {
+                write!(
+                    f,
+                    "Throttled: at least one response is 429 Too Many Requests"
+                )
+            }
+            ClientExpectation::NotThrottled => {
+                write!(f, "Not throttled: no response is 429 Too Many Requests")
+            }
+        }
+    }
+}
+
+impl ClientExpectation {
+    fn verify(&self, responses: &[&Response]) -> (bool, String) {
+        let num_throttled = responses
+            .iter()
+            .filter(|response| response.status == 429)
+            .count();
+
+        match self {
+            ClientExpectation::Throttled => (
+                num_throttled > 0,
+                format!("Expected to be throttled, received {num_throttled} 429 responses"),
+            ),
+            ClientExpectation::NotThrottled => (
+                num_throttled == 0,
+                format!("Expected not to be throttled, received {num_throttled} 429 responses"),
+            ),
         }
     }
 }
@@ -248,6 +349,8 @@ pub enum HttpMethod {
     Post,
     Put,
     Delete,
+    Head,
+    Options,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -268,7 +371,9 @@ pub fn client_system(
         if let ClientState::SendNextRequest = client.state {
             // Send first request
             let idx = client.curr_request_idx;
-            if let Some(request_config) = client.request_configs.get_mut(idx) {
+            let request_config = client.all_request_configs_mut().nth(idx);
+
+            if let Some(request_config) = request_config {
                 let recipient = hostname_connections
                     .get_connected_entity_by_hostname(client_entity, &request_config.url);
 
@@ -280,7 +385,7 @@ pub fn client_system(
                         sender: client_entity,
                         recipients: vec![recipient],
                         message: Message::Request(request),
-                        trace_id,
+                        span: SpanContext::root(trace_id),
                     });
 
                     client.state = ClientState::Waiting(trace_id);
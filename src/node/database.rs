@@ -1,9 +1,10 @@
 use std::collections::{HashMap, VecDeque};
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
-use crate::message::{DatabaseCallType, Message, MessageComponent, SendMessageEvent};
+use crate::message::{DatabaseCallType, Message, MessageComponent, Response, SendMessageEvent};
 
 use super::SystemNodeTrait;
 
@@ -14,8 +15,13 @@ pub struct Database {
     pub documents: HashMap<i32, Document>,
     initial_documents: HashMap<i32, Document>,
     state: DatabaseState,
+    // Whether saves and deletes are refused.
+    pub read_only: bool,
     message_queue: VecDeque<MessageComponent>,
     can_be_edited: bool,
+    // What the documents must be once the simulation finishes.
+    expectations: Vec<DatabaseExpectation>,
+    pub expectations_results: Vec<(bool, String)>,
 }
 
 impl Database {
@@ -24,8 +30,11 @@ impl Database {
             documents: Default::default(),
             initial_documents: Default::default(),
             state: Default::default(),
+            read_only: false,
             message_queue: Default::default(),
             can_be_edited: true,
+            expectations: vec![],
+            expectations_results: vec![],
         }
     }
 
@@ -34,14 +43,52 @@ impl Database {
         self
     }
 
+    pub fn expectations(mut self, expectations: Vec<DatabaseExpectation>) -> Self {
+        self.expectations = expectations;
+        self
+    }
+
+    pub fn get_expectations(&self) -> &[DatabaseExpectation] {
+        &self.expectations
+    }
+
+    pub fn expectations_mut(&mut self) -> &mut Vec<DatabaseExpectation> {
+        &mut self.expectations
+    }
+
+    pub fn verify(&mut self) -> bool {
+        self.expectations_results = self
+            .expectations
+            .iter()
+            .map(|expectation| expectation.verify(&self.documents))
+            .collect();
+
+        self.expectations_results.iter().all(|(passed, _)| *passed)
+    }
+
     pub fn initial_documents(mut self, initial_documents: Vec<Document>) -> Self {
+        self.set_initial_documents(initial_documents);
+        self
+    }
+
+    pub fn set_initial_documents(&mut self, initial_documents: Vec<Document>) {
         self.initial_documents = HashMap::from_iter(
             initial_documents
                 .into_iter()
                 .map(|d| (d.get("id").unwrap().as_i64().unwrap() as i32, d)),
         );
         self.documents = self.initial_documents.clone();
-        self
+    }
+
+    /// The initial documents, ordered by id.
+    pub fn get_initial_documents(&self) -> Vec<Document> {
+        let mut documents: Vec<_> = self.initial_documents.iter().collect();
+        documents.sort_by_key(|(id, _)| **id);
+
+        documents
+            .into_iter()
+            .map(|(_, document)| document.clone())
+            .collect()
     }
 
     fn save(&mut self, mut doc: Document) -> Document {
@@ -73,10 +120,80 @@ impl Database {
         self.documents.remove(&id);
     }
 
+    pub fn is_down(&self) -> bool {
+        self.state == DatabaseState::Down
+    }
+
+    pub fn crash(&mut self) {
+        self.state = DatabaseState::Down;
+    }
+
+    pub fn recover(&mut self) {
+        self.state = DatabaseState::Active;
+    }
+
     fn reset(&mut self) {
         self.state = DatabaseState::SimulationNotStarted;
+        self.read_only = false;
         self.documents = self.initial_documents.clone();
         self.message_queue.drain(..);
+        self.expectations_results = vec![];
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum DatabaseExpectation {
+    // A document has every field of the given object, with the same values.
+    Contains(Value),
+    Count(usize),
+    // No document has the given id.
+    Absent(Value),
+}
+
+impl DatabaseExpectation {
+    fn verify(&self, documents: &HashMap<i32, Document>) -> (bool, String) {
+        match self {
+            DatabaseExpectation::Contains(expected) => {
+                let passed = documents.values().any(|document| match expected {
+                    Value::Object(fields) => fields
+                        .iter()
+                        .all(|(name, value)| document.get(name) == Some(value)),
+                    _ => false,
+                });
+
+                let mut msg = format!("Expected a document matching {expected}");
+
+                if !passed {
+                    msg.push_str(", found none");
+                }
+
+                (passed, msg)
+            }
+            DatabaseExpectation::Count(expected) => {
+                let passed = documents.len() == *expected;
+
+                let mut msg = format!("Expected {expected} documents");
+
+                if !passed {
+                    msg.push_str(&format!(", found {}", documents.len()));
+                }
+
+                (passed, msg)
+            }
+            DatabaseExpectation::Absent(id) => {
+                let passed = !documents
+                    .values()
+                    .any(|document| document.get("id") == Some(id));
+
+                let mut msg = format!("Expected no document with id {id}");
+
+                if !passed {
+                    msg.push_str(", found one");
+                }
+
+                (passed, msg)
+            }
+        }
     }
 }
 
@@ -87,9 +204,6 @@ impl SystemNodeTrait for Database {
 
     fn handle_message(&mut self, message: MessageComponent) {
         if self.state == DatabaseState::Active {
-            println!("HANDLING MESSAGE FOR DATABASE:");
-            println!("{:?}", message);
-
             self.message_queue.push_back(message);
         }
     }
@@ -108,6 +222,7 @@ pub enum DatabaseState {
     #[default]
     SimulationNotStarted,
     Active,
+    Down,
 }
 
 pub fn database_system(
@@ -123,7 +238,36 @@ pub fn database_system(
             let message_queue = database.message_queue.drain(..).collect::<Vec<_>>();
 
             for message in message_queue {
+                if let Message::HealthCheck = message.message {
+                    events.send(SendMessageEvent {
+                        sender: database_entity,
+                        recipients: vec![message.sender],
+                        message: Message::Response(Response::healthy()),
+                        span: message.span,
+                    });
+
+                    continue;
+                }
+
                 if let Message::DatabaseCall(db_call) = message.message {
+                    if database.read_only
+                        && matches!(
+                            db_call.call_type,
+                            DatabaseCallType::Save(_) | DatabaseCallType::Delete(_)
+                        )
+                    {
+                        events.send(SendMessageEvent {
+                            sender: database_entity,
+                            recipients: vec![message.sender],
+                            message: Message::DatabaseError(
+                                "The Database is read-only.".to_string(),
+                            ),
+                            span: message.span,
+                        });
+
+                        continue;
+                    }
+
                     let db_answer_value = match db_call.call_type {
                         DatabaseCallType::Save(value) => {
                             let document = serde_json::from_value::<Document>(value);
@@ -158,10 +302,51 @@ pub fn database_system(
                         sender: database_entity,
                         recipients: vec![message.sender],
                         message: Message::DatabaseAnswer(db_answer_value),
-                        trace_id: message.trace_id,
+                        span: message.span,
                     });
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn should_verify_final_documents() {
+        let documents: Vec<Document> = serde_json::from_value(json!([
+            { "id": 0, "name": "John", "age": 51 },
+            { "id": 2, "name": "Jane", "age": 28 }
+        ]))
+        .unwrap();
+        let mut database = Database::new()
+            .initial_documents(documents)
+            .expectations(vec![
+                DatabaseExpectation::Contains(json!({ "name": "Jane", "age": 28 })),
+                DatabaseExpectation::Count(2),
+                DatabaseExpectation::Absent(json!(1)),
+            ]);
+
+        assert!(database.verify());
+
+        database.delete(2);
+
+        assert!(!database.verify());
+        assert_eq!(
+            vec![
+                (
+                    false,
+                    "Expected a document matching {\"age\":28,\"name\":\"Jane\"}, found none"
+                        .to_string()
+                ),
+                (false, "Expected 2 documents, found 1".to_string()),
+                (true, "Expected no document with id 1".to_string()),
+            ],
+            database.expectations_results
+        );
+    }
+}
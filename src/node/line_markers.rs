@@ -0,0 +1,339 @@
+/// The global variable that holds the position of the latest statement to start running. Boa does
+/// not report where a runtime error was thrown, so scripts are marked with it before they run.
+pub const POSITION_VARIABLE: &str = "__position";
+
+/// The global variable counted up by every marked statement, which measures how much work a script
+/// does the same way on every machine.
+pub const STATEMENT_COUNTER: &str = "__statements";
+
+// Words that continue the statement on the line before, rather than start a new one.
+const CONTINUATION_WORDS: [&str; 8] = [
+    "else",
+    "catch",
+    "finally",
+    "case",
+    "default",
+    "in",
+    "instanceof",
+    "of",
+];
+
+// Words after which a `/` starts a regular expression rather than a division.
+const OPERATOR_WORDS: [&str; 10] = [
+    "return", "typeof", "case", "do", "else", "in", "new", "delete", "void", "throw",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Delimiter {
+    Block,
+    Object,
+    Paren,
+    Bracket,
+    // A `${` substitution within a template literal.
+    Substitution,
+}
+
+// The kind of the previous token, which is all that is needed to tell where statements start.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Previous<'a> {
+    Start,
+    Semicolon,
+    OpenBrace(Delimiter),
+    CloseBrace,
+    CloseParen,
+    Arrow,
+    Word(&'a str),
+    // A literal or a closing bracket, after which a `/` is a division.
+    Value,
+    Other,
+}
+
+impl Previous<'_> {
+    fn opens_block(&self) -> bool {
+        match self {
+            Previous::Start
+            | Previous::Semicolon
+            | Previous::OpenBrace(Delimiter::Block)
+            | Previous::CloseBrace
+            | Previous::CloseParen
+            | Previous::Arrow => true,
+            Previous::Word(word) => ["else", "try", "finally", "do"].contains(word),
+            _ => false,
+        }
+    }
+
+    fn ends_statement(&self) -> bool {
+        matches!(
+            self,
+            Previous::Start
+                | Previous::Semicolon
+                | Previous::OpenBrace(Delimiter::Block)
+                | Previous::CloseBrace
+        )
+    }
+
+    fn starts_regex(&self) -> bool {
+        match self {
+            Previous::Value | Previous::CloseParen | Previous::CloseBrace => false,
+            Previous::Word(word) => OPERATOR_WORDS.contains(word),
+            _ => true,
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Adds an assignment of `position(line)` to `POSITION_VARIABLE`, and an increment of
+/// `STATEMENT_COUNTER`, before each statement that starts a line, on the same line so that the line numbers of syntax errors do not change. Lines that
+/// cannot be told apart from the continuation of an expression are left unmarked, so the result
+/// should still be checked to parse.
+pub fn mark_lines(script: &str, position: impl Fn(usize) -> String) -> String {
+    let chars: Vec<(usize, char)> = script.char_indices().collect();
+    let char_at = |idx: usize| chars.get(idx).map(|(_, c)| *c);
+    let word_at = |start: usize| {
+        let end = (start..chars.len())
+            .find(|idx| !is_word_char(chars[*idx].1))
+            .unwrap_or(chars.len());
+        let end_offset = chars.get(end).map_or(script.len(), |(offset, _)| *offset);
+        (&script[chars[start].0..end_offset], end)
+    };
+
+    let mut markers = vec![];
+    let mut stack = vec![];
+    let mut previous = Previous::Start;
+    let mut line = 1;
+    let mut at_line_start = true;
+    let mut in_template = false;
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        let c = chars[idx].1;
+
+        if in_template {
+            match c {
+                '\\' => idx += 1,
+                '\n' => line += 1,
+                '`' => {
+                    in_template = false;
+                    previous = Previous::Value;
+                }
+                '$' if char_at(idx + 1) == Some('{') => {
+                    in_template = false;
+                    stack.push(Delimiter::Substitution);
+                    previous = Previous::Other;
+                    idx += 1;
+                }
+                _ => {}
+            }
+
+            idx += 1;
+            continue;
+        }
+
+        if c == '\n' {
+            line += 1;
+            at_line_start = true;
+            idx += 1;
+            continue;
+        }
+
+        if c.is_whitespace() {
+            idx += 1;
+            continue;
+        }
+
+        if c == '/' && char_at(idx + 1) == Some('/') {
+            while char_at(idx).is_some_and(|c| c != '\n') {
+                idx += 1;
+            }
+            continue;
+        }
+
+        if c == '/' && char_at(idx + 1) == Some('*') {
+            idx += 2;
+            while idx < chars.len() && !(chars[idx].1 == '*' && char_at(idx + 1) == Some('/')) {
+                if chars[idx].1 == '\n' {
+                    line += 1;
+                    at_line_start = true;
+                }
+                idx += 1;
+            }
+            idx += 2;
+            continue;
+        }
+
+        if at_line_start {
+            at_line_start = false;
+
+            let in_statement_list = matches!(stack.last(), None | Some(Delimiter::Block));
+
+            if in_statement_list && previous.ends_statement() && is_word_char(c) {
+                let (word, _) = word_at(idx);
+
+                let continues_statement = CONTINUATION_WORDS.contains(&word)
+                    || (word == "while" && previous == Previous::CloseBrace);
+
+                if !continues_statement && !c.is_ascii_digit() {
+                    markers.push((chars[idx].0, line));
+                }
+            }
+        }
+
+        match c {
+            '\'' | '"' => {
+                idx += 1;
+                while let Some(next) = char_at(idx) {
+                    match next {
+                        '\\' => idx += 1,
+                        '\n' => line += 1,
+                        next if next == c => break,
+                        _ => {}
+                    }
+                    idx += 1;
+                }
+                previous = Previous::Value;
+            }
+            '`' => in_template = true,
+            '{' => {
+                let delimiter = if previous.opens_block() {
+                    Delimiter::Block
+                } else {
+                    Delimiter::Object
+                };
+                stack.push(delimiter);
+                previous = Previous::OpenBrace(delimiter);
+            }
+            '}' => {
+                if stack.pop() == Some(Delimiter::Substitution) {
+                    in_template = true;
+                } else {
+                    previous = Previous::CloseBrace;
+                }
+            }
+            '(' => {
+                stack.push(Delimiter::Paren);
+                previous = Previous::Other;
+            }
+            '[' => {
+                stack.push(Delimiter::Bracket);
+                previous = Previous::Other;
+            }
+            ')' => {
+                stack.pop();
+                previous = Previous::CloseParen;
+            }
+            ']' => {
+                stack.pop();
+                previous = Previous::Value;
+            }
+            ';' => previous = Previous::Semicolon,
+            '=' if char_at(idx + 1) == Some('>') => {
+                previous = Previous::Arrow;
+                idx += 1;
+            }
+            '/' if previous.starts_regex() => {
+                let mut in_class = false;
+                idx += 1;
+                while let Some(next) = char_at(idx) {
+                    match next {
+                        '\\' => idx += 1,
+                        '[' => in_class = true,
+                        ']' => in_class = false,
+                        '/' if !in_class => break,
+                        '\n' => break,
+                        _ => {}
+                    }
+                    idx += 1;
+                }
+                previous = Previous::Value;
+            }
+            c if is_word_char(c) => {
+                let (word, end) = word_at(idx);
+                previous = if c.is_ascii_digit() {
+                    Previous::Value
+                } else {
+                    Previous::Word(word)
+                };
+                idx = end;
+                continue;
+            }
+            _ => previous = Previous::Other,
+        }
+
+        idx += 1;
+    }
+
+    let mut marked = String::with_capacity(script.len());
+    let mut copied = 0;
+
+    for (offset, line) in markers {
+        let position = serde_json::to_string(&position(line)).unwrap();
+
+        marked.push_str(&script[copied..offset]);
+        marked.push_str(&format!(
+            "{POSITION_VARIABLE} = {position}; {STATEMENT_COUNTER}++; "
+        ));
+        copied = offset;
+    }
+
+    marked.push_str(&script[copied..]);
+
+    marked
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn marked_lines(script: &str) -> Vec<usize> {
+        mark_lines(script, |line| line.to_string())
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.contains(POSITION_VARIABLE))
+            .map(|(idx, _)| idx + 1)
+            .collect()
+    }
+
+    #[test]
+    fn should_mark_statements_at_the_start_of_lines() {
+        let script = r#"const requestHandler = function* (request) {
+  const user = yield db.findOne("users", request.params.id);
+  if (!user) {
+    throw new Error("No user");
+  } else {
+    console.log(`Found ${user.name}`);
+  }
+  return response(200, user);
+}"#;
+
+        assert_eq!(vec![1, 2, 3, 4, 6, 8], marked_lines(script));
+        assert_eq!(
+            r#"  __position = "line 2"; __statements++; const user = yield db.findOne("users", request.params.id);"#,
+            mark_lines(script, |line| format!("line {line}"))
+                .lines()
+                .nth(1)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn should_not_mark_lines_that_continue_an_expression() {
+        let script = r#"const requestHandler = function* () {
+  const data = {
+    status: "ok",
+    items: [1, 2]
+      .map((item) => item * 2),
+  };
+  const total = data.items.length
+    + 1;
+  // A comment; with a semicolon {
+  const text = `first line;
+second line`;
+  return response(200, { data, total, text, regex: /[;{]/ });
+}"#;
+
+        assert_eq!(vec![1, 2, 7, 10, 12], marked_lines(script));
+    }
+}
@@ -9,7 +9,11 @@ use crate::message::MessageComponent;
 
 pub mod client;
 pub mod database;
+pub mod line_markers;
+pub mod rate_limit;
+pub mod router;
 pub mod server;
+pub mod upstream;
 
 #[derive(Bundle)]
 pub struct SystemNodeBundle {
@@ -45,6 +49,10 @@ pub trait SystemNodeTrait {
 #[derive(Component)]
 pub struct SystemNode;
 
+/// Marks a node placed by the level that cannot be moved or deleted.
+#[derive(Component)]
+pub struct Locked;
+
 #[derive(Component, Clone, Copy, Debug, strum::Display, PartialEq, Eq)]
 pub enum NodeType {
     Client,
@@ -77,6 +85,15 @@ impl NodeName {
     fn new() -> Self {
         Default::default()
     }
+
+    /// The name to show for a node, falling back to its type for unnamed nodes.
+    pub fn label(&self, node_type: &NodeType, entity: Entity) -> String {
+        if self.0.is_empty() {
+            format!("{} {}", node_type, entity.index())
+        } else {
+            self.0.clone()
+        }
+    }
 }
 
 #[derive(Default, Component, Clone)]
@@ -138,7 +155,7 @@ impl NodeConnections {
         self.connections.remove(&other_node);
     }
 
-    pub fn iter(&self) -> Iter<Entity, Entity> {
+    pub fn iter(&self) -> Iter<'_, Entity, Entity> {
         self.connections.iter()
     }
 
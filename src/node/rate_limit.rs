@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use bevy::prelude::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::message::Request;
+
+/// Limits how many requests a Server accepts from each sender or API key, in simulation time.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    pub key: RateLimitKey,
+    pub algorithm: RateLimitAlgorithm,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            key: RateLimitKey::Sender,
+            algorithm: RateLimitAlgorithm::TokenBucket {
+                capacity: 5,
+                refill_per_second: 1.0,
+            },
+        }
+    }
+}
+
+impl RateLimit {
+    /// Whether the limit ever accepts a request, and refills at a rate that can be waited for.
+    pub fn is_valid(&self) -> bool {
+        match self.algorithm {
+            RateLimitAlgorithm::TokenBucket {
+                capacity,
+                refill_per_second,
+            } => capacity > 0 && refill_per_second.is_finite() && refill_per_second > 0.0,
+            RateLimitAlgorithm::SlidingWindow { limit, window_ms } => limit > 0 && window_ms > 0,
+        }
+    }
+
+    // Requests without the header share a single limit.
+    fn key_of(&self, sender: Entity, request: &Request) -> String {
+        match &self.key {
+            RateLimitKey::Sender => format!("{sender:?}"),
+            RateLimitKey::Header(name) => request.headers.get(name).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RateLimitKey {
+    // The node the request came from.
+    Sender,
+    // The value of a request header, such as an API key.
+    Header(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum RateLimitAlgorithm {
+    // Each request takes a token, and tokens are added back at a steady rate, so bursts of up to
+    // `capacity` requests are accepted.
+    TokenBucket {
+        capacity: u32,
+        refill_per_second: f32,
+    },
+    // At most `limit` requests are accepted in any `window_ms`.
+    SlidingWindow {
+        limit: u32,
+        window_ms: u64,
+    },
+}
+
+/// The requests each key has made against a Server's rate limit.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter {
+    // The tokens left in each bucket, and when they were last refilled.
+    buckets: HashMap<String, (f32, Duration)>,
+    // When each accepted request within the window was made.
+    windows: HashMap<String, VecDeque<Duration>>,
+    num_throttled: usize,
+}
+
+impl RateLimiter {
+    /// Accepts a request, or returns how long the sender must wait before its next request could
+    /// be accepted.
+    pub fn check(
+        &mut self,
+        rate_limit: &RateLimit,
+        sender: Entity,
+        request: &Request,
+        now: Duration,
+    ) -> Result<(), Duration> {
+        let key = rate_limit.key_of(sender, request);
+
+        let result = match rate_limit.algorithm {
+            RateLimitAlgorithm::TokenBucket {
+                capacity,
+                refill_per_second,
+            } => {
+                let capacity = capacity as f32;
+                let (tokens, refilled_at) = self.buckets.entry(key).or_insert((capacity, now));
+
+                *tokens = (*tokens + (now - *refilled_at).as_secs_f32() * refill_per_second)
+                    .min(capacity);
+                *refilled_at = now;
+
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    Ok(())
+                } else {
+                    // A rate too slow to wait for never lets the sender through again.
+                    Err(
+                        Duration::try_from_secs_f32((1.0 - *tokens) / refill_per_second)
+                            .unwrap_or(Duration::MAX),
+                    )
+                }
+            }
+            RateLimitAlgorithm::SlidingWindow { limit, window_ms } => {
+                let window = Duration::from_millis(window_ms);
+                let requests = self.windows.entry(key).or_default();
+
+                while requests
+                    .front()
+                    .is_some_and(|requested_at| *requested_at + window <= now)
+                {
+                    requests.pop_front();
+                }
+
+                if requests.len() < limit as usize {
+                    requests.push_back(now);
+                    Ok(())
+                } else {
+                    // A request is accepted again once the oldest one leaves the window.
+                    Err(requests
+                        .front()
+                        .map(|requested_at| *requested_at + window - now)
+                        .unwrap_or(window))
+                }
+            }
+        };
+
+        if result.is_err() {
+            self.num_throttled += 1;
+        }
+
+        result
+    }
+
+    /// The number of requests rejected so far.
+    pub fn num_throttled(&self) -> usize {
+        self.num_throttled
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request_with_api_key(api_key: &str) -> Request {
+        let mut request = Request::default();
+        request
+            .headers
+            .insert("X-Api-Key".to_string(), api_key.to_string());
+        request
+    }
+
+    #[test]
+    fn should_refill_token_bucket_over_time() {
+        let rate_limit = RateLimit {
+            key: RateLimitKey::Sender,
+            algorithm: RateLimitAlgorithm::TokenBucket {
+                capacity: 2,
+                refill_per_second: 0.5,
+            },
+        };
+        let mut limiter = RateLimiter::default();
+        let sender = Entity::from_raw(1);
+        let request = Request::default();
+        let at = Duration::from_millis;
+
+        assert!(limiter.check(&rate_limit, sender, &request, at(0)).is_ok());
+        assert!(limiter.check(&rate_limit, sender, &request, at(0)).is_ok());
+        assert_eq!(
+            Err(at(1000)),
+            limiter.check(&rate_limit, sender, &request, at(1000))
+        );
+
+        // Other senders have their own bucket.
+        assert!(limiter
+            .check(&rate_limit, Entity::from_raw(2), &request, at(1000))
+            .is_ok());
+
+        assert!(limiter
+            .check(&rate_limit, sender, &request, at(2000))
+            .is_ok());
+        assert_eq!(1, limiter.num_throttled());
+    }
+
+    #[test]
+    fn should_not_panic_on_rates_too_slow_to_wait_for() {
+        let sender = Entity::from_raw(1);
+        let request = Request::default();
+
+        let token_bucket = |refill_per_second| RateLimit {
+            key: RateLimitKey::Sender,
+            algorithm: RateLimitAlgorithm::TokenBucket {
+                capacity: 1,
+                refill_per_second,
+            },
+        };
+
+        for refill_per_second in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(!token_bucket(refill_per_second).is_valid());
+        }
+
+        for refill_per_second in [0.0, -1.0, 1e-30] {
+            let rate_limit = token_bucket(refill_per_second);
+            let mut limiter = RateLimiter::default();
+
+            assert!(limiter
+                .check(&rate_limit, sender, &request, Duration::ZERO)
+                .is_ok());
+            assert_eq!(
+                Err(Duration::MAX),
+                limiter.check(&rate_limit, sender, &request, Duration::ZERO)
+            );
+        }
+    }
+
+    #[test]
+    fn should_limit_requests_per_api_key_within_sliding_window() {
+        let rate_limit = RateLimit {
+            key: RateLimitKey::Header("X-Api-Key".to_string()),
+            algorithm: RateLimitAlgorithm::SlidingWindow {
+                limit: 2,
+                window_ms: 1000,
+            },
+        };
+        let mut limiter = RateLimiter::default();
+        let at = Duration::from_millis;
+
+        // The key decides the limit, whichever node the request came from.
+        let mut check = |sender, api_key, ms| {
+            limiter.check(
+                &rate_limit,
+                Entity::from_raw(sender),
+                &request_with_api_key(api_key),
+                at(ms),
+            )
+        };
+
+        assert!(check(1, "abc", 0).is_ok());
+        assert!(check(2, "abc", 400).is_ok());
+        assert_eq!(Err(at(200)), check(1, "abc", 800));
+        assert!(check(1, "xyz", 800).is_ok());
+        assert!(check(1, "abc", 1000).is_ok());
+    }
+}
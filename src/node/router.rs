@@ -0,0 +1,521 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use serde_json::Value;
+use strum::IntoEnumIterator;
+
+use super::client::HttpMethod;
+
+const DEFAULT_WILDCARD_NAME: &str = "wildcard";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Segment {
+    Static(String),
+    Param { name: String, kind: ParamKind },
+    Wildcard(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum ParamKind {
+    Any,
+    Int,
+}
+
+impl Segment {
+    // Lower ranks take precedence when two routes could match the same segment.
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 0,
+            Segment::Param {
+                kind: ParamKind::Int,
+                ..
+            } => 1,
+            Segment::Param {
+                kind: ParamKind::Any,
+                ..
+            } => 2,
+            Segment::Wildcard(_) => 3,
+        }
+    }
+
+    // The segment with its binding name erased, used to detect routes that are indistinguishable.
+    fn shape(&self) -> Segment {
+        match self {
+            Segment::Static(s) => Segment::Static(s.clone()),
+            Segment::Param { kind, .. } => Segment::Param {
+                name: String::new(),
+                kind: *kind,
+            },
+            Segment::Wildcard(_) => Segment::Wildcard(String::new()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatternError {
+    MissingLeadingSlash,
+    TrailingSlash,
+    EmptySegment,
+    EmptyParamName,
+    UnknownParamType(String),
+    DuplicateParam(String),
+    WildcardNotLast,
+    OptionalNotTrailing,
+}
+
+impl Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternError::MissingLeadingSlash => write!(f, "Path must start with '/'."),
+            PatternError::TrailingSlash => write!(f, "Path must not end with '/'."),
+            PatternError::EmptySegment => write!(f, "Path must not contain empty segments."),
+            PatternError::EmptyParamName => write!(f, "Path parameters must be named."),
+            PatternError::UnknownParamType(t) => write!(f, "Unknown parameter type '{t}'."),
+            PatternError::DuplicateParam(p) => write!(f, "Duplicate parameter '{p}'."),
+            PatternError::WildcardNotLast => write!(f, "Wildcard must be the last segment."),
+            PatternError::OptionalNotTrailing => {
+                write!(f, "Optional segments must be at the end of the path.")
+            }
+        }
+    }
+}
+
+/// A parsed endpoint path such as `/users/:id(int)`, `/posts/:page?` or `/static/*file`.
+///
+/// Optional segments are expanded into one variant per possible segment count.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoutePattern {
+    variants: Vec<Vec<Segment>>,
+}
+
+impl RoutePattern {
+    pub fn parse(path: &str) -> Result<Self, PatternError> {
+        let rest = path
+            .strip_prefix('/')
+            .ok_or(PatternError::MissingLeadingSlash)?;
+
+        if rest.is_empty() {
+            return Ok(Self {
+                variants: vec![vec![]],
+            });
+        }
+
+        if rest.ends_with('/') {
+            return Err(PatternError::TrailingSlash);
+        }
+
+        let raw_segments: Vec<_> = rest.split('/').collect();
+        let mut segments = vec![];
+        let mut num_required = None;
+        let mut names = HashSet::new();
+
+        for (idx, raw) in raw_segments.iter().enumerate() {
+            if raw.is_empty() {
+                return Err(PatternError::EmptySegment);
+            }
+
+            let (raw, optional) = match raw.strip_suffix('?') {
+                Some(raw) if raw.starts_with(':') => (raw, true),
+                _ => (*raw, false),
+            };
+
+            if optional {
+                num_required.get_or_insert(idx);
+            } else if num_required.is_some() {
+                return Err(PatternError::OptionalNotTrailing);
+            }
+
+            let segment = if let Some(param) = raw.strip_prefix(':') {
+                let (name, kind) = parse_param(param)?;
+                Segment::Param { name, kind }
+            } else if let Some(name) = raw.strip_prefix('*') {
+                if idx != raw_segments.len() - 1 {
+                    return Err(PatternError::WildcardNotLast);
+                }
+                let name = if name.is_empty() {
+                    DEFAULT_WILDCARD_NAME
+                } else {
+                    name
+                };
+                Segment::Wildcard(name.to_string())
+            } else {
+                Segment::Static(raw.to_string())
+            };
+
+            if let Segment::Param { name, .. } | Segment::Wildcard(name) = &segment {
+                if !names.insert(name.clone()) {
+                    return Err(PatternError::DuplicateParam(name.clone()));
+                }
+            }
+
+            segments.push(segment);
+        }
+
+        let num_required = num_required.unwrap_or(segments.len());
+
+        Ok(Self {
+            variants: (num_required..=segments.len())
+                .map(|len| segments[..len].to_vec())
+                .collect(),
+        })
+    }
+
+    fn shapes(&self) -> HashSet<Vec<Segment>> {
+        self.variants
+            .iter()
+            .map(|v| v.iter().map(Segment::shape).collect())
+            .collect()
+    }
+
+    /// Returns true if both patterns can match exactly the same URLs, so neither takes precedence.
+    pub fn is_ambiguous_with(&self, other: &RoutePattern) -> bool {
+        !self.shapes().is_disjoint(&other.shapes())
+    }
+}
+
+fn parse_param(param: &str) -> Result<(String, ParamKind), PatternError> {
+    let (name, kind) = match param.strip_suffix(')').and_then(|p| p.split_once('(')) {
+        Some((name, "int")) => (name, ParamKind::Int),
+        Some((_, t)) => return Err(PatternError::UnknownParamType(t.to_string())),
+        None => (param, ParamKind::Any),
+    };
+
+    if name.is_empty() {
+        return Err(PatternError::EmptyParamName);
+    }
+
+    Ok((name.to_string(), kind))
+}
+
+fn match_segments(segments: &[Segment], url_segments: &[&str]) -> Option<HashMap<String, Value>> {
+    let mut params = HashMap::new();
+
+    for (idx, segment) in segments.iter().enumerate() {
+        match segment {
+            Segment::Wildcard(name) => {
+                let rest = &url_segments[idx.min(url_segments.len())..];
+                if rest.is_empty() || rest.iter().any(|s| s.is_empty()) {
+                    return None;
+                }
+                params.insert(name.clone(), Value::from(rest.join("/")));
+                return Some(params);
+            }
+            _ if idx >= url_segments.len() => return None,
+            Segment::Static(s) if s != url_segments[idx] => return None,
+            Segment::Static(_) => {}
+            Segment::Param { name, kind } => {
+                let value = url_segments[idx];
+                let value = match kind {
+                    _ if value.is_empty() => return None,
+                    ParamKind::Any => Value::from(value),
+                    ParamKind::Int => Value::from(value.parse::<i64>().ok()?),
+                };
+                params.insert(name.clone(), value);
+            }
+        }
+    }
+
+    (segments.len() == url_segments.len()).then_some(params)
+}
+
+fn compare_precedence(a: &[Segment], b: &[Segment]) -> Ordering {
+    a.iter()
+        .map(Segment::rank)
+        .cmp(b.iter().map(Segment::rank))
+        .then_with(|| b.len().cmp(&a.len()))
+}
+
+#[derive(Clone, Debug)]
+struct Route {
+    segments: Vec<Segment>,
+    method: HttpMethod,
+    endpoint_idx: usize,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct RouteMatch {
+    pub endpoint_idx: usize,
+    pub params: HashMap<String, Value>,
+}
+
+/// Matches request paths to endpoints, trying static segments before parameters before wildcards.
+///
+/// Endpoints whose path fails to parse are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new<'a>(endpoints: impl IntoIterator<Item = (&'a str, HttpMethod)>) -> Self {
+        let mut routes: Vec<_> = endpoints
+            .into_iter()
+            .enumerate()
+            .filter_map(|(endpoint_idx, (path, method))| {
+                RoutePattern::parse(path).ok().map(|pattern| {
+                    pattern.variants.into_iter().map(move |segments| Route {
+                        segments,
+                        method,
+                        endpoint_idx,
+                    })
+                })
+            })
+            .flatten()
+            .collect();
+
+        // Stable sort, so declaration order only breaks ties between ambiguous routes.
+        routes.sort_by(|a, b| compare_precedence(&a.segments, &b.segments));
+
+        Self { routes }
+    }
+
+    pub fn find(&self, method: HttpMethod, url: &str) -> Option<RouteMatch> {
+        let url_segments = split_url(url);
+
+        self.routes
+            .iter()
+            .filter(|route| route.method == method)
+            .find_map(|route| {
+                match_segments(&route.segments, &url_segments).map(|params| RouteMatch {
+                    endpoint_idx: route.endpoint_idx,
+                    params,
+                })
+            })
+    }
+
+    /// Returns the methods that have a route matching the URL, including the implicit `HEAD` for
+    /// any `GET` route and `OPTIONS`, or an empty list if no route matches the URL at all.
+    pub fn allowed_methods(&self, url: &str) -> Vec<HttpMethod> {
+        let url_segments = split_url(url);
+
+        let methods: HashSet<_> = self
+            .routes
+            .iter()
+            .filter(|route| match_segments(&route.segments, &url_segments).is_some())
+            .map(|route| route.method)
+            .collect();
+
+        if methods.is_empty() {
+            return vec![];
+        }
+
+        HttpMethod::iter()
+            .filter(|method| match method {
+                HttpMethod::Head => methods.contains(&HttpMethod::Get) || methods.contains(method),
+                HttpMethod::Options => true,
+                _ => methods.contains(method),
+            })
+            .collect()
+    }
+}
+
+fn split_url(url: &str) -> Vec<&str> {
+    let url = url.strip_prefix('/').unwrap_or(url);
+
+    if url.is_empty() {
+        vec![]
+    } else {
+        url.split('/').collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn find(url: &str, paths: &[&str]) -> Option<(String, HashMap<String, Value>)> {
+        Router::new(paths.iter().map(|p| (*p, HttpMethod::Get)))
+            .find(HttpMethod::Get, url)
+            .map(|m| (paths[m.endpoint_idx].to_string(), m.params))
+    }
+
+    fn params<const N: usize>(entries: [(&str, Value); N]) -> HashMap<String, Value> {
+        entries
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect()
+    }
+
+    #[test]
+    fn should_return_matching_path_and_empty_params_for_root() {
+        assert_eq!(Some(("/".to_string(), params([]))), find("/", &["/"]))
+    }
+
+    #[test]
+    fn should_return_matching_path_with_single_param_after_root() {
+        assert_eq!(
+            Some(("/:id".to_string(), params([("id", Value::from("1"))]))),
+            find("/1", &["/:id"])
+        )
+    }
+
+    #[test]
+    fn should_return_matching_path_with_single_param() {
+        let paths = [
+            "/orders",
+            "/orders/:id",
+            "/orders/:id/items",
+            "/users",
+            "/users/:id",
+            "/users/:id/messages",
+        ];
+
+        assert_eq!(
+            Some((
+                "/orders/:id".to_string(),
+                params([("id", Value::from("1"))])
+            )),
+            find("/orders/1", &paths)
+        )
+    }
+
+    #[test]
+    fn should_return_matching_path_with_multiple_params() {
+        let paths = [
+            "/orders",
+            "/orders/:orderId",
+            "/orders/:orderId/items",
+            "/orders/:orderId/items/:itemId",
+            "/orders/:orderId/items/:itemId/id",
+        ];
+
+        assert_eq!(
+            Some((
+                "/orders/:orderId/items/:itemId".to_string(),
+                params([
+                    ("orderId", Value::from("123")),
+                    ("itemId", Value::from("456"))
+                ])
+            )),
+            find("/orders/123/items/456", &paths)
+        )
+    }
+
+    #[test]
+    fn should_return_none_for_no_matching_path() {
+        let paths = [
+            "/users",
+            "/users/:userId/messages",
+            "/orders/:orderId/items",
+            "/orders/:orderId/items/:itemId",
+        ];
+
+        assert_eq!(None, find("/users/123", &paths))
+    }
+
+    #[test]
+    fn should_prefer_static_over_param_over_wildcard_regardless_of_order() {
+        let paths = ["/files/*", "/files/:name", "/files/readme"];
+
+        assert_eq!(
+            Some(("/files/readme".to_string(), params([]))),
+            find("/files/readme", &paths)
+        );
+        assert_eq!(
+            Some((
+                "/files/:name".to_string(),
+                params([("name", Value::from("a"))])
+            )),
+            find("/files/a", &paths)
+        );
+        assert_eq!(
+            Some((
+                "/files/*".to_string(),
+                params([("wildcard", Value::from("a/b"))])
+            )),
+            find("/files/a/b", &paths)
+        );
+    }
+
+    #[test]
+    fn should_bind_typed_int_params_as_numbers() {
+        let paths = ["/users/:name", "/users/:id(int)"];
+
+        assert_eq!(
+            Some((
+                "/users/:id(int)".to_string(),
+                params([("id", Value::from(7))])
+            )),
+            find("/users/7", &paths)
+        );
+        assert_eq!(
+            Some((
+                "/users/:name".to_string(),
+                params([("name", Value::from("bob"))])
+            )),
+            find("/users/bob", &paths)
+        );
+    }
+
+    #[test]
+    fn should_match_optional_trailing_segments() {
+        let paths = ["/posts/:page(int)?"];
+
+        assert_eq!(
+            Some(("/posts/:page(int)?".to_string(), params([]))),
+            find("/posts", &paths)
+        );
+        assert_eq!(
+            Some((
+                "/posts/:page(int)?".to_string(),
+                params([("page", Value::from(2))])
+            )),
+            find("/posts/2", &paths)
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_patterns() {
+        assert_eq!(
+            Err(PatternError::WildcardNotLast),
+            RoutePattern::parse("/a/*/b")
+        );
+        assert_eq!(
+            Err(PatternError::OptionalNotTrailing),
+            RoutePattern::parse("/:a?/b")
+        );
+        assert_eq!(
+            Err(PatternError::UnknownParamType("uuid".to_string())),
+            RoutePattern::parse("/:id(uuid)")
+        );
+        assert_eq!(Err(PatternError::TrailingSlash), RoutePattern::parse("/a/"));
+    }
+
+    #[test]
+    fn should_return_allowed_methods_for_matching_path() {
+        let router = Router::new([
+            ("/users", HttpMethod::Get),
+            ("/users", HttpMethod::Post),
+            ("/users/:id", HttpMethod::Delete),
+        ]);
+
+        assert_eq!(None, router.find(HttpMethod::Put, "/users"));
+        assert_eq!(
+            vec![
+                HttpMethod::Get,
+                HttpMethod::Post,
+                HttpMethod::Head,
+                HttpMethod::Options
+            ],
+            router.allowed_methods("/users")
+        );
+        assert_eq!(
+            vec![HttpMethod::Delete, HttpMethod::Options],
+            router.allowed_methods("/users/1")
+        );
+        assert!(router.allowed_methods("/orders").is_empty());
+    }
+
+    #[test]
+    fn should_detect_ambiguous_patterns() {
+        let parse = |p| RoutePattern::parse(p).unwrap();
+
+        assert!(parse("/users/:id").is_ambiguous_with(&parse("/users/:name")));
+        assert!(parse("/users/:id?").is_ambiguous_with(&parse("/users")));
+        assert!(!parse("/users/:id(int)").is_ambiguous_with(&parse("/users/:name")));
+        assert!(!parse("/users/me").is_ambiguous_with(&parse("/users/:id")));
+    }
+}
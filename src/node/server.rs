@@ -1,37 +1,115 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt::Display,
+    time::{Duration, Instant},
+};
 
-use bevy::prelude::{warn, Component, Entity, EventWriter, Query};
-use boa_engine::{property::Attribute, Context, JsResult, JsValue};
+use bevy::prelude::{debug, warn, Component, Entity, EventWriter, Query, Res, ResMut};
+use boa_engine::{property::Attribute, Context, JsError, JsValue, Script, Source};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
 
-use crate::message::{
-    DatabaseCall, Message, MessageComponent, Request, Response, SendMessageEvent,
+use crate::{
+    console::{ConsoleLog, ConsoleMessage},
+    message::{
+        DatabaseCall, Message, MessageComponent, Request, Response, SendMessageEvent, SpanContext,
+    },
+    simulation::{SimulationClock, SimulationRng},
 };
 
-use super::{client::HttpMethod, HostnameConnections, SystemNodeTrait};
+use super::{
+    client::{Client, ClientState, HttpMethod},
+    line_markers::{mark_lines, POSITION_VARIABLE, STATEMENT_COUNTER},
+    rate_limit::{RateLimit, RateLimiter},
+    router::{PatternError, RouteMatch, RoutePattern, Router},
+    upstream::{is_failure, retry_after, CallOptions, CircuitBreaker},
+    HostnameConnections, SystemNodeTrait,
+};
 
 #[derive(Component, Clone, Debug)]
 pub struct Server {
     pub endpoint_handlers: Vec<Endpoint>,
+    pub middlewares: Vec<Middleware>,
+    // The `state` object shared by all handlers of this server, persisted between requests.
+    pub local_state: Value,
     pub message_queue: VecDeque<MessageComponent>,
     pub state: ServerState,
     active_executions: HashMap<Uuid, ServerExecution>,
+    // Steps waiting for a free worker, in arrival order.
+    step_queue: VecDeque<ServerExecution>,
+    // Steps that have run on a worker, whose results are held back until their processing time
+    // has elapsed.
+    processing_steps: Vec<ProcessingStep>,
     can_be_edited: bool,
     max_concurrent_connections: Option<u8>,
+    cpu_cost: Option<f32>,
+    base_cost: Duration,
+    step_cost: Duration,
+    workers: Option<u8>,
+    max_backlog: Option<u8>,
+    pub health_checks: HealthChecks,
+    // The upstream hostnames whose latest health check failed.
+    unhealthy_upstreams: BTreeSet<String>,
+    // The upstream hostname of each health check waiting for an answer, by span id.
+    pending_health_checks: HashMap<Uuid, String>,
+    next_health_check_at: Duration,
+    // The upstream request of each execution waiting for a response, by span id.
+    pending_calls: HashMap<Uuid, UpstreamCall>,
+    // Failed upstream requests waiting out their backoff before being sent again.
+    retries: Vec<PendingRetry>,
+    // By upstream hostname.
+    circuit_breakers: BTreeMap<String, CircuitBreaker>,
+    pub rate_limit: Option<RateLimit>,
+    rate_limiter: RateLimiter,
 }
 
-#[derive(Clone, Debug)]
+/// The path of the built-in health endpoint, answered by every Server and Database.
+pub const HEALTH_PATH: &str = "/health";
+
+/// The upstreams a server probes on its health endpoint, every `interval_ms` of simulation time.
+/// An upstream is down once a probe fails, or is still unanswered when the next one is due.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HealthChecks {
+    pub hostnames: Vec<String>,
+    pub interval_ms: u64,
+}
+
+impl Default for HealthChecks {
+    fn default() -> Self {
+        Self {
+            hostnames: vec![],
+            interval_ms: 1000,
+        }
+    }
+}
+
+// Handlers are sandboxed by limiting the iterations of each loop and the depth of calls, plus a
+// wall-clock cap on each step for slow built-in calls.
+const LOOP_ITERATION_LIMIT: u64 = 100_000;
+const RECURSION_LIMIT: usize = 256;
+const STEP_WALL_CLOCK_BUDGET: Duration = Duration::from_millis(100);
+// The longest a handler can sleep for in a single step.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Endpoint {
     pub path: String,
     pub method: HttpMethod,
     pub handler: String,
+    // Endpoints written by the level that the player cannot change or delete.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub locked: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 impl Endpoint {
     fn is_path_valid(&self) -> bool {
-        self.path.starts_with('/') && (self.path.len() == 1 || !self.path.ends_with('/'))
+        RoutePattern::parse(&self.path).is_ok()
     }
 
     pub fn is_handler_valid(&self) -> bool {
@@ -39,16 +117,42 @@ impl Endpoint {
             return false;
         }
 
-        let mut context = Context::default();
-
-        context.parse(&self.handler).is_ok()
+        is_script_valid(&self.handler)
     }
 
-    fn is_valid(&self) -> bool {
+    pub fn is_valid(&self) -> bool {
         self.is_path_valid() && self.is_handler_valid()
     }
 }
 
+/// A handler that wraps every matched endpoint handler, in the order the middlewares are listed.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Middleware {
+    pub handler: String,
+}
+
+impl Middleware {
+    pub fn is_handler_valid(&self) -> bool {
+        if self.handler.is_empty() {
+            return false;
+        }
+
+        is_script_valid(&self.handler)
+    }
+}
+
+const EXAMPLE_MIDDLEWARE: &str = r#"const middleware = function* (request, next) {
+  return yield* next();
+}"#;
+
+impl Default for Middleware {
+    fn default() -> Self {
+        Self {
+            handler: EXAMPLE_MIDDLEWARE.to_string(),
+        }
+    }
+}
+
 const EXAMPLE_REQUEST_HANDLER: &str = r#"const requestHandler = function* () {
   return response(200, "Ok");
 }"#;
@@ -59,6 +163,7 @@ impl Default for Endpoint {
             path: "/".to_string(),
             method: HttpMethod::Post,
             handler: EXAMPLE_REQUEST_HANDLER.to_string(),
+            locked: false,
         }
     }
 }
@@ -69,9 +174,27 @@ impl Default for Server {
             message_queue: Default::default(),
             state: Default::default(),
             active_executions: Default::default(),
+            step_queue: Default::default(),
+            processing_steps: Default::default(),
             endpoint_handlers: vec![Endpoint::default()],
+            middlewares: vec![],
+            local_state: Value::Object(Default::default()),
             can_be_edited: true,
             max_concurrent_connections: None,
+            cpu_cost: None,
+            base_cost: Duration::ZERO,
+            step_cost: Duration::ZERO,
+            workers: None,
+            max_backlog: None,
+            health_checks: Default::default(),
+            unhealthy_upstreams: Default::default(),
+            pending_health_checks: Default::default(),
+            next_health_check_at: Duration::ZERO,
+            pending_calls: Default::default(),
+            retries: Default::default(),
+            circuit_breakers: Default::default(),
+            rate_limit: None,
+            rate_limiter: Default::default(),
         }
     }
 }
@@ -79,12 +202,46 @@ impl Default for Server {
 impl Server {
     fn can_handle_new_request(&self) -> bool {
         if let Some(max_concurrent_connections) = self.max_concurrent_connections {
-            let num_current_connections = self.active_executions.len();
-            return num_current_connections < max_concurrent_connections.into();
+            return self.num_connections() < max_concurrent_connections.into();
         };
         true
     }
 
+    /// The size of all handler code, ignoring whitespace so formatting does not count.
+    pub fn handler_size(&self) -> usize {
+        self.endpoint_handlers
+            .iter()
+            .map(|e| &e.handler)
+            .chain(self.middlewares.iter().map(|m| &m.handler))
+            .flat_map(|handler| handler.chars())
+            .filter(|c| !c.is_whitespace())
+            .count()
+    }
+
+    /// The number of requests being handled, including those waiting on upstream calls.
+    pub fn num_connections(&self) -> usize {
+        self.active_executions.len()
+            + self.step_queue.len()
+            + self.processing_steps.len()
+            + self.retries.len()
+    }
+
+    // Whether a new request can start or wait for a worker, rather than be rejected with a 503.
+    fn can_queue_new_request(&self) -> bool {
+        match (self.workers, self.max_backlog) {
+            (Some(workers), Some(max_backlog)) => {
+                let num_queued_steps = self.processing_steps.len() + self.step_queue.len();
+                num_queued_steps < usize::from(workers) + usize::from(max_backlog)
+            }
+            _ => true,
+        }
+    }
+
+    fn has_free_worker(&self) -> bool {
+        self.workers
+            .is_none_or(|workers| self.processing_steps.len() < workers.into())
+    }
+
     pub fn with_max_concurrent_connections(
         mut self,
         max_concurrent_connections: Option<u8>,
@@ -97,94 +254,340 @@ impl Server {
         self.max_concurrent_connections
     }
 
+    pub fn with_cpu_cost(mut self, cpu_cost: Option<f32>) -> Self {
+        self.cpu_cost = cpu_cost;
+        self
+    }
+
+    pub fn cpu_cost(&self) -> Option<f32> {
+        self.cpu_cost
+    }
+
+    pub fn with_processing_costs(mut self, base_cost: Duration, step_cost: Duration) -> Self {
+        self.base_cost = base_cost;
+        self.step_cost = step_cost;
+        self
+    }
+
+    pub fn base_cost(&self) -> Duration {
+        self.base_cost
+    }
+
+    pub fn step_cost(&self) -> Duration {
+        self.step_cost
+    }
+
+    pub fn with_worker_pool(mut self, workers: Option<u8>, max_backlog: Option<u8>) -> Self {
+        self.workers = workers;
+        self.max_backlog = max_backlog;
+        self
+    }
+
+    pub fn workers(&self) -> Option<u8> {
+        self.workers
+    }
+
+    pub fn max_backlog(&self) -> Option<u8> {
+        self.max_backlog
+    }
+
+    pub fn num_busy_workers(&self) -> usize {
+        self.processing_steps.len()
+    }
+
+    pub fn num_queued_steps(&self) -> usize {
+        self.step_queue.len()
+    }
+
+    /// Whether the upstream with this hostname passed its latest health check. Upstreams that are
+    /// not health checked are always up.
+    pub fn is_upstream_up(&self, hostname: &str) -> bool {
+        !self.unhealthy_upstreams.contains(hostname)
+    }
+
+    // A server that would reject a new request reports itself as unavailable.
+    fn health_response(&self) -> Response {
+        if self.can_handle_new_request() && self.can_queue_new_request() {
+            Response::healthy()
+        } else {
+            Response::service_unavailable()
+        }
+    }
+
+    // The hostnames to probe, if a round of health checks is due. Upstreams that have not
+    // answered the previous round are marked as down.
+    fn due_health_checks(&mut self, now: Duration) -> Vec<String> {
+        if self.health_checks.hostnames.is_empty() || now < self.next_health_check_at {
+            return vec![];
+        }
+
+        for (_, hostname) in self.pending_health_checks.drain() {
+            self.unhealthy_upstreams.insert(hostname);
+        }
+
+        self.next_health_check_at = now + Duration::from_millis(self.health_checks.interval_ms);
+
+        self.health_checks.hostnames.clone()
+    }
+
+    fn start_health_check(&mut self, span_id: Uuid, hostname: String) {
+        self.pending_health_checks.insert(span_id, hostname);
+    }
+
+    fn set_upstream_health(&mut self, hostname: String, up: bool) {
+        if up {
+            self.unhealthy_upstreams.remove(&hostname);
+        } else {
+            self.unhealthy_upstreams.insert(hostname);
+        }
+    }
+
+    // Returns whether the response answers a health check.
+    fn finish_health_check(&mut self, span_id: Uuid, response: &Response) -> bool {
+        let Some(hostname) = self.pending_health_checks.remove(&span_id) else {
+            return false;
+        };
+
+        self.set_upstream_health(hostname, (200..300).contains(&response.status));
+
+        true
+    }
+
+    // Requests over the rate limit are rejected before any other check.
+    fn check_rate_limit(
+        &mut self,
+        sender: Entity,
+        request: &Request,
+        now: Duration,
+    ) -> Result<(), Response> {
+        match &self.rate_limit {
+            Some(rate_limit) => self
+                .rate_limiter
+                .check(rate_limit, sender, request, now)
+                .map_err(Response::too_many_requests),
+            None => Ok(()),
+        }
+    }
+
+    /// The number of requests rejected by the rate limit in the current simulation.
+    pub fn num_throttled(&self) -> usize {
+        self.rate_limiter.num_throttled()
+    }
+
+    pub fn circuit_breakers(&self) -> impl Iterator<Item = (&String, &CircuitBreaker)> {
+        self.circuit_breakers.iter()
+    }
+
+    fn clear_health(&mut self) {
+        self.unhealthy_upstreams.clear();
+        self.pending_health_checks.clear();
+        self.next_health_check_at = Duration::ZERO;
+    }
+
+    // How long a worker is busy with a step, which depends only on the handler code and its inputs.
+    fn processing_time(&self, execution: &ServerExecution, step: &ExecutionStep) -> Duration {
+        let base_cost = if execution.yield_values.is_empty() {
+            self.base_cost
+        } else {
+            Duration::ZERO
+        };
+
+        let cpu_time = self
+            .cpu_cost
+            .and_then(|cpu_cost| {
+                Duration::try_from_secs_f32(cpu_cost * step.statements as f32 / 1_000_000.0).ok()
+            })
+            .unwrap_or_default();
+
+        let sleep_time = match &step.result {
+            Ok((
+                GeneratorResultValue {
+                    done: false,
+                    value: YieldValue::Sleep(ms),
+                },
+                _,
+            )) => Duration::try_from_secs_f64(ms.max(0.0) / 1000.0)
+                .unwrap_or(MAX_SLEEP)
+                .min(MAX_SLEEP),
+            _ => Duration::ZERO,
+        };
+
+        base_cost + self.step_cost + cpu_time + sleep_time
+    }
+
+    fn router(&self) -> Router {
+        Router::new(
+            self.endpoint_handlers
+                .iter()
+                .map(|e| (e.path.as_str(), e.method)),
+        )
+    }
+
+    // HEAD requests fall back to the GET endpoint for the path.
+    fn find_route(router: &Router, method: HttpMethod, path: &str) -> Option<RouteMatch> {
+        match method {
+            HttpMethod::Head => router
+                .find(HttpMethod::Head, path)
+                .or_else(|| router.find(HttpMethod::Get, path)),
+            method => router.find(method, path),
+        }
+    }
+
+    /// The endpoint that handles requests with this method and path.
+    pub fn matched_endpoint(&self, method: HttpMethod, path: &str) -> Option<&Endpoint> {
+        Self::find_route(&self.router(), method, path)
+            .map(|route_match| &self.endpoint_handlers[route_match.endpoint_idx])
+    }
+
+    // Returns an early response instead of an execution when no handler should run, e.g. for
+    // unknown paths, unsupported methods, or automatically answered OPTIONS requests.
     fn create_execution_for_request(
         &mut self,
         mut request: Request,
         original_sender: Entity,
-        original_trace_id: Uuid,
-    ) -> Option<ServerExecution> {
-        let endpoints_by_method: HashMap<HttpMethod, HashMap<String, String>> =
-            self.endpoint_handlers.clone().into_iter().fold(
-                HashMap::new(),
-                |mut acc,
-                 Endpoint {
-                     path,
-                     method,
-                     handler,
-                 }| {
-                    acc.entry(method).or_default().insert(path, handler);
-                    acc
-                },
-            );
+        original_span: SpanContext,
+    ) -> Result<ServerExecution, Response> {
+        let router = self.router();
 
-        endpoints_by_method
-            .get(&request.method)
-            .and_then(|endpoints_by_path| {
-                map_url_to_path_with_params(&request.path, endpoints_by_path.keys().collect())
-            })
-            .map(|EndpointMatch { path, params }| {
+        let route_match = Self::find_route(&router, request.method, &request.path);
+
+        match route_match {
+            Some(RouteMatch {
+                endpoint_idx,
+                params,
+            }) => {
                 request.params = params;
-                ServerExecution::new(
-                    endpoints_by_method
-                        .get(&request.method)
-                        .unwrap()
-                        .get(path)
-                        .unwrap()
-                        .to_string(),
+                Ok(ServerExecution::new(
+                    self.endpoint_handlers[endpoint_idx].handler.clone(),
+                    self.middlewares.iter().map(|m| m.handler.clone()).collect(),
                     request,
                     original_sender,
-                    original_trace_id,
-                )
-            })
+                    original_span,
+                ))
+            }
+            None if request.path == HEALTH_PATH
+                && matches!(request.method, HttpMethod::Get | HttpMethod::Head) =>
+            {
+                Err(self.health_response())
+            }
+            None => {
+                let allowed_methods = router.allowed_methods(&request.path);
+
+                if allowed_methods.is_empty() {
+                    Err(ExecutionError::NotFound.into())
+                } else if request.method == HttpMethod::Options {
+                    Err(Response::no_content().with_allow_header(&allowed_methods))
+                } else {
+                    Err(ExecutionError::MethodNotAllowed(allowed_methods).into())
+                }
+            }
+        }
     }
 
     fn reset(&mut self) {
         self.state = ServerState::SimulationNotStarted;
         self.message_queue.drain(..);
         self.active_executions.clear();
+        self.step_queue.clear();
+        self.processing_steps.clear();
+        self.local_state = Value::Object(Default::default());
+        self.clear_health();
+        self.clear_upstream_calls();
+        self.rate_limiter = Default::default();
     }
 
-    pub fn is_path_valid(&self, idx: usize) -> bool {
-        if !self.endpoint_handlers[idx].is_path_valid() {
-            return false;
-        }
+    fn clear_upstream_calls(&mut self) {
+        self.pending_calls.clear();
+        self.retries.clear();
+        self.circuit_breakers.clear();
+    }
 
-        let mut endpoints = self.endpoint_handlers.clone();
-        let endpoint_to_check = endpoints.swap_remove(idx);
+    /// Takes the server down, losing its state and every request it was handling. Returns the
+    /// sender and span of each of those requests, which are answered with an error.
+    pub fn crash(&mut self) -> Vec<(Entity, SpanContext)> {
+        self.state = ServerState::Down;
+        self.local_state = Value::Object(Default::default());
+        self.clear_health();
+
+        let mut open_requests: Vec<_> = self
+            .message_queue
+            .drain(..)
+            .filter(|message| matches!(message.message, Message::Request(_)))
+            .map(|message| (message.sender, message.span))
+            .collect();
+
+        let executions = self
+            .active_executions
+            .drain()
+            .map(|(_, execution)| execution)
+            .chain(self.step_queue.drain(..))
+            .chain(self.processing_steps.drain(..).map(|step| step.execution))
+            .chain(self.retries.drain(..).map(|retry| retry.execution));
+
+        open_requests.extend(
+            executions.map(|execution| (execution.original_sender, execution.original_span)),
+        );
 
-        let other_endpoints: HashSet<(String, HttpMethod)> =
-            HashSet::from_iter(endpoints.iter().map(|e| (strip_params(&e.path), e.method)));
+        self.clear_upstream_calls();
 
-        let endpoint = (
-            strip_params(&endpoint_to_check.path),
-            endpoint_to_check.method,
-        );
+        open_requests
+    }
 
-        !other_endpoints.contains(&endpoint)
+    pub fn recover(&mut self) {
+        self.state = ServerState::Active;
     }
 
-    pub fn is_valid(&self) -> bool {
-        let unique_endpoints: HashSet<(String, HttpMethod)> = HashSet::from_iter(
-            self.endpoint_handlers
-                .iter()
-                .map(|e| (strip_params(&e.path), e.method)),
-        );
+    pub fn validate_path(&self, idx: usize) -> Result<(), PathError> {
+        let endpoint = &self.endpoint_handlers[idx];
+        let pattern = RoutePattern::parse(&endpoint.path).map_err(PathError::Invalid)?;
 
-        if unique_endpoints.len() != self.endpoint_handlers.len() {
-            return false;
+        let ambiguous_with: Vec<_> = self
+            .endpoint_handlers
+            .iter()
+            .enumerate()
+            .filter(|(other_idx, other)| *other_idx != idx && other.method == endpoint.method)
+            .filter(|(_, other)| {
+                RoutePattern::parse(&other.path)
+                    .map(|other_pattern| pattern.is_ambiguous_with(&other_pattern))
+                    .unwrap_or(false)
+            })
+            .map(|(other_idx, _)| other_idx)
+            .collect();
+
+        if ambiguous_with.is_empty() {
+            Ok(())
+        } else {
+            Err(PathError::AmbiguousWith(ambiguous_with))
         }
+    }
 
-        self.endpoint_handlers.iter().all(|e| e.is_valid())
+    pub fn is_path_valid(&self, idx: usize) -> bool {
+        self.validate_path(idx).is_ok()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (0..self.endpoint_handlers.len()).all(|idx| self.is_path_valid(idx))
+            && self.endpoint_handlers.iter().all(|e| e.is_valid())
+            && self.middlewares.iter().all(|m| m.is_handler_valid())
     }
 }
 
-fn strip_params(path: &str) -> String {
-    let segments: Vec<_> = path
-        .split('/')
-        .map(|seg| if seg.starts_with(':') { "param" } else { seg })
-        .collect();
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathError {
+    Invalid(PatternError),
+    AmbiguousWith(Vec<usize>),
+}
 
-    segments.join("/")
+impl Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathError::Invalid(error) => error.fmt(f),
+            PathError::AmbiguousWith(others) => {
+                let others: Vec<_> = others.iter().map(|idx| format!("#{}", idx + 1)).collect();
+                write!(f, "Ambiguous with endpoint {}.", others.join(", "))
+            }
+        }
+    }
 }
 
 impl SystemNodeTrait for Server {
@@ -194,9 +597,6 @@ impl SystemNodeTrait for Server {
 
     fn handle_message(&mut self, message: MessageComponent) {
         if self.state == ServerState::Active {
-            println!("HANDLING MESSAGE FOR SERVER:");
-            println!("{:?}", message);
-
             self.message_queue.push_back(message);
         }
     }
@@ -215,6 +615,7 @@ pub enum ServerState {
     #[default]
     SimulationNotStarted,
     Active,
+    Down,
 }
 
 #[allow(clippy::single_match)]
@@ -222,34 +623,68 @@ pub fn server_system(
     mut server_query: Query<(Entity, &mut Server)>,
     mut events: EventWriter<SendMessageEvent>,
     hostname_connections: HostnameConnections,
+    mut console_log: ResMut<ConsoleLog>,
+    simulation_clock: Res<SimulationClock>,
+    mut rng: ResMut<SimulationRng>,
+    clients: Query<&Client>,
 ) {
+    let now = simulation_clock.elapsed();
+
+    // Health checks stop once every Client has finished, so that no more messages are sent and the
+    // simulation can finish.
+    let clients_finished = clients
+        .iter()
+        .all(|client| client.state == ClientState::Finished);
+
     for (server_entity, mut server) in server_query.iter_mut() {
         match server.state {
             ServerState::Active => {
-                if server.message_queue.is_empty() {
-                    continue;
-                }
-
                 let message_queue = server.message_queue.drain(..).collect::<Vec<_>>();
 
                 for message in message_queue {
                     let handle_message_result = match message.message {
                         Message::Request(request) => {
-                            if server.can_handle_new_request() {
-                                server
-                                    .create_execution_for_request(
-                                        request,
-                                        message.sender,
-                                        message.trace_id,
-                                    )
-                                    .ok_or(ExecutionError::NotFound)
+                            if let Err(response) =
+                                server.check_rate_limit(message.sender, &request, now)
+                            {
+                                Err(response)
+                            } else if server.can_handle_new_request()
+                                && server.can_queue_new_request()
+                            {
+                                server.create_execution_for_request(
+                                    request,
+                                    message.sender,
+                                    message.span,
+                                )
                             } else {
-                                Err(ExecutionError::ServiceUnavailable)
+                                Err(ExecutionError::ServiceUnavailable.into())
                             }
                         }
+                        Message::HealthCheck => Err(server.health_response()),
                         Message::Response(response) => {
-                            let mut execution =
-                                server.active_executions.remove(&message.trace_id).unwrap();
+                            if server.finish_health_check(message.span.span_id, &response) {
+                                continue;
+                            }
+
+                            // Executions are lost when the server crashes.
+                            let Some(execution) =
+                                server.active_executions.remove(&message.span.span_id)
+                            else {
+                                continue;
+                            };
+
+                            let Some(mut execution) = finish_upstream_call(
+                                server_entity,
+                                &mut server,
+                                execution,
+                                message.span.span_id,
+                                &response,
+                                &mut console_log,
+                                now,
+                                rng.0.gen(),
+                            ) else {
+                                continue;
+                            };
 
                             execution
                                 .yield_values
@@ -258,8 +693,11 @@ pub fn server_system(
                             Ok(execution)
                         }
                         Message::DatabaseAnswer(answer) => {
-                            let mut execution =
-                                server.active_executions.remove(&message.trace_id).unwrap();
+                            let Some(mut execution) =
+                                server.active_executions.remove(&message.span.span_id)
+                            else {
+                                continue;
+                            };
 
                             execution
                                 .yield_values
@@ -267,158 +705,512 @@ pub fn server_system(
 
                             Ok(execution)
                         }
-                        _ => Err(ExecutionError::BadRequest),
+                        Message::DatabaseError(reason) => {
+                            let Some(execution) =
+                                server.active_executions.remove(&message.span.span_id)
+                            else {
+                                continue;
+                            };
+
+                            console_log.push(
+                                server_entity,
+                                now,
+                                execution.original_span.trace_id,
+                                ConsoleMessage::error(format!("Database call failed: {reason}")),
+                            );
+
+                            events.send(SendMessageEvent {
+                                sender: server_entity,
+                                recipients: vec![execution.original_sender],
+                                message: Message::Response(Response::internal_server_error(
+                                    reason.into(),
+                                )),
+                                span: execution.original_span,
+                            });
+
+                            continue;
+                        }
+                        _ => Err(ExecutionError::BadRequest.into()),
                     };
 
                     match handle_message_result {
-                        Ok(execution) => {
-                            let res = execution.execute();
-
-                            println!("{:?}", res);
-
-                            match res {
-                                Ok(res) => {
-                                    match (res.done, res.value) {
-                                        (true, YieldValue::Response(response)) => {
-                                            events.send(SendMessageEvent {
-                                                sender: server_entity,
-                                                recipients: vec![execution.original_sender],
-                                                message: Message::Response(response),
-                                                trace_id: execution.original_trace_id,
-                                            });
-                                        }
-                                        (false, YieldValue::Request(new_request)) => {
-                                            let new_trace_id = Uuid::new_v4();
-
-                                            let recipient = hostname_connections
-                                                .get_connected_entity_by_hostname(
-                                                    server_entity,
-                                                    &new_request.url,
-                                                );
-
-                                            if let Some(recipient) = recipient {
-                                                events.send(SendMessageEvent {
-                                                    sender: server_entity,
-                                                    recipients: vec![recipient],
-                                                    message: Message::Request(new_request),
-                                                    trace_id: new_trace_id,
-                                                });
-
-                                                server
-                                                    .active_executions
-                                                    .insert(new_trace_id, execution);
-                                            } else {
-                                                events.send(SendMessageEvent {
-                                                    sender: server_entity,
-                                                    recipients: vec![execution.original_sender],
-                                                    message: Message::Response(
-                                                        Response::internal_server_error(
-                                                            "Upstream request refused.".into(),
-                                                        ),
-                                                    ),
-                                                    trace_id: execution.original_trace_id,
-                                                });
-                                            }
-                                        }
-                                        (false, YieldValue::DatabaseCall(database_call)) => {
-                                            let recipient = hostname_connections
-                                                .get_connected_entity_by_hostname(
-                                                    server_entity,
-                                                    &database_call.name,
-                                                );
-
-                                            match recipient {
-                                                Some(recipient) => {
-                                                    let new_trace_id = Uuid::new_v4();
-
-                                                    events.send(SendMessageEvent {
-                                                        sender: server_entity,
-                                                        recipients: vec![recipient],
-                                                        message: Message::DatabaseCall(
-                                                            database_call,
-                                                        ),
-                                                        trace_id: new_trace_id,
-                                                    });
-
-                                                    server
-                                                        .active_executions
-                                                        .insert(new_trace_id, execution);
-                                                }
-                                                None => {
-                                                    events.send(SendMessageEvent {
-                                                        sender: server_entity,
-                                                        recipients: vec![execution.original_sender],
-                                                        message: Message::Response(
-                                                            Response::internal_server_error(
-                                                                "Upstream request refused.".into(),
-                                                            ),
-                                                        ),
-                                                        trace_id: execution.original_trace_id,
-                                                    });
-                                                }
-                                            };
-                                        }
-                                        _ => warn!("Unexpected yield value"),
-                                    };
-                                }
-                                Err(execution_error) => {
-                                    events.send(SendMessageEvent {
-                                        sender: server_entity,
-                                        recipients: vec![message.sender],
-                                        message: Message::Response(execution_error.into()),
-                                        trace_id: message.trace_id,
-                                    });
-                                }
-                            }
-                        }
-                        Err(execution_error) => {
+                        Ok(execution) => server.step_queue.push_back(execution),
+                        Err(response) => {
                             events.send(SendMessageEvent {
                                 sender: server_entity,
                                 recipients: vec![message.sender],
-                                message: Message::Response(execution_error.into()),
-                                trace_id: message.trace_id,
+                                message: Message::Response(response),
+                                span: message.span,
                             });
                         }
                     };
                 }
+
+                let (due_retries, retries) = server
+                    .retries
+                    .drain(..)
+                    .partition::<Vec<_>, _>(|retry| retry.ready_at <= now);
+
+                server.retries = retries;
+
+                for PendingRetry {
+                    execution, call, ..
+                } in due_retries
+                {
+                    send_upstream_call(
+                        server_entity,
+                        &mut server,
+                        execution,
+                        call,
+                        &mut events,
+                        &hostname_connections,
+                        &mut console_log,
+                        now,
+                    );
+                }
+
+                let due_health_checks = if clients_finished {
+                    vec![]
+                } else {
+                    server.due_health_checks(now)
+                };
+
+                for hostname in due_health_checks {
+                    let recipient = hostname_connections
+                        .get_connected_entity_by_hostname(server_entity, &hostname);
+
+                    let Some(recipient) = recipient else {
+                        server.set_upstream_health(hostname, false);
+                        continue;
+                    };
+
+                    // Health checks are not part of any Client request, so each starts its own trace.
+                    let span = SpanContext::root(Uuid::new_v4());
+
+                    events.send(SendMessageEvent {
+                        sender: server_entity,
+                        recipients: vec![recipient],
+                        message: Message::HealthCheck,
+                        span,
+                    });
+
+                    server.start_health_check(span.span_id, hostname);
+                }
+
+                while server.has_free_worker() {
+                    let Some(mut execution) = server.step_queue.pop_front() else {
+                        break;
+                    };
+
+                    execution.state_snapshots.push(server.local_state.clone());
+                    execution
+                        .health_snapshots
+                        .push(server.unhealthy_upstreams.iter().cloned().collect());
+
+                    let step = execution.execute();
+
+                    let ready_at = now + server.processing_time(&execution, &step);
+
+                    for console_message in step.console_output {
+                        console_log.push(
+                            server_entity,
+                            now,
+                            execution.original_span.trace_id,
+                            console_message,
+                        );
+                    }
+
+                    let result = step.result.map(|(res, local_state)| {
+                        server.local_state = local_state;
+                        res
+                    });
+
+                    server.processing_steps.push(ProcessingStep {
+                        ready_at,
+                        execution,
+                        result,
+                    });
+                }
+
+                let (ready_steps, processing_steps) = server
+                    .processing_steps
+                    .drain(..)
+                    .partition::<Vec<_>, _>(|step| step.ready_at <= now);
+
+                server.processing_steps = processing_steps;
+
+                for step in ready_steps {
+                    complete_step(
+                        server_entity,
+                        &mut server,
+                        step,
+                        &mut events,
+                        &hostname_connections,
+                        &mut console_log,
+                        now,
+                    );
+                }
             }
             _ => {}
         };
     }
 }
 
+#[derive(Clone, Debug)]
+struct ProcessingStep {
+    ready_at: Duration,
+    execution: ServerExecution,
+    result: Result<GeneratorResultValue, ExecutionError>,
+}
+
+// Sends out the result of a step once the server has finished processing it.
+#[allow(clippy::too_many_arguments)]
+fn complete_step(
+    server_entity: Entity,
+    server: &mut Server,
+    ProcessingStep {
+        execution, result, ..
+    }: ProcessingStep,
+    events: &mut EventWriter<SendMessageEvent>,
+    hostname_connections: &HostnameConnections,
+    console_log: &mut ConsoleLog,
+    now: Duration,
+) {
+    match result {
+        Ok(res) => match (res.done, res.value) {
+            (true, YieldValue::Response(mut response)) => {
+                if execution.request.method == HttpMethod::Head {
+                    response.data = Value::Null;
+                }
+
+                events.send(SendMessageEvent {
+                    sender: server_entity,
+                    recipients: vec![execution.original_sender],
+                    message: Message::Response(response),
+                    span: execution.original_span,
+                });
+            }
+            (false, YieldValue::Request(mut new_request)) => {
+                let options = new_request.options.take().unwrap_or_default();
+
+                send_upstream_call(
+                    server_entity,
+                    server,
+                    execution,
+                    UpstreamCall {
+                        request: new_request,
+                        options,
+                        retry: 0,
+                    },
+                    events,
+                    hostname_connections,
+                    console_log,
+                    now,
+                );
+            }
+            (false, YieldValue::DatabaseCall(database_call)) => {
+                let recipient = hostname_connections
+                    .get_connected_entity_by_hostname(server_entity, &database_call.name);
+
+                match recipient {
+                    Some(recipient) => {
+                        let span = execution.original_span.child();
+
+                        events.send(SendMessageEvent {
+                            sender: server_entity,
+                            recipients: vec![recipient],
+                            message: Message::DatabaseCall(database_call),
+                            span,
+                        });
+
+                        server.active_executions.insert(span.span_id, execution);
+                    }
+                    None => {
+                        console_log.push(
+                            server_entity,
+                            now,
+                            execution.original_span.trace_id,
+                            ConsoleMessage::error(format!(
+                                "Database call to {} refused.",
+                                database_call.name
+                            )),
+                        );
+
+                        events.send(SendMessageEvent {
+                            sender: server_entity,
+                            recipients: vec![execution.original_sender],
+                            message: Message::Response(Response::internal_server_error(
+                                "Upstream request refused.".into(),
+                            )),
+                            span: execution.original_span,
+                        });
+                    }
+                };
+            }
+            (false, YieldValue::Sleep(_)) => {
+                // The sleep is over once its processing time has elapsed, so continue straight away.
+                let mut execution = execution;
+
+                execution.yield_values.push(Value::Null);
+                server.step_queue.push_back(execution);
+            }
+            _ => warn!("Unexpected yield value"),
+        },
+        Err(execution_error) => {
+            if let Some(log_message) = execution_error.log_message() {
+                console_log.push(
+                    server_entity,
+                    now,
+                    execution.original_span.trace_id,
+                    ConsoleMessage::error(log_message),
+                );
+            }
+
+            events.send(SendMessageEvent {
+                sender: server_entity,
+                recipients: vec![execution.original_sender],
+                message: Message::Response(execution_error.into()),
+                span: execution.original_span,
+            });
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct UpstreamCall {
+    request: Request,
+    options: CallOptions,
+    // The number of times the request has already been retried.
+    retry: u32,
+}
+
+#[derive(Clone, Debug)]
+struct PendingRetry {
+    ready_at: Duration,
+    execution: ServerExecution,
+    call: UpstreamCall,
+}
+
+// Sends an upstream request of an execution, unless the circuit breaker of the upstream is open,
+// in which case the execution continues straight away with an error.
+#[allow(clippy::too_many_arguments)]
+fn send_upstream_call(
+    server_entity: Entity,
+    server: &mut Server,
+    mut execution: ServerExecution,
+    call: UpstreamCall,
+    events: &mut EventWriter<SendMessageEvent>,
+    hostname_connections: &HostnameConnections,
+    console_log: &mut ConsoleLog,
+    now: Duration,
+) {
+    let recipient =
+        hostname_connections.get_connected_entity_by_hostname(server_entity, &call.request.url);
+
+    let Some(recipient) = recipient else {
+        console_log.push(
+            server_entity,
+            now,
+            execution.original_span.trace_id,
+            ConsoleMessage::error(format!("Upstream request to {} refused.", call.request.url)),
+        );
+
+        events.send(SendMessageEvent {
+            sender: server_entity,
+            recipients: vec![execution.original_sender],
+            message: Message::Response(Response::internal_server_error(
+                "Upstream request refused.".into(),
+            )),
+            span: execution.original_span,
+        });
+
+        return;
+    };
+
+    if call.options.circuit_breaker.is_some() {
+        let breaker = server
+            .circuit_breakers
+            .entry(call.request.url.clone())
+            .or_default();
+
+        if !breaker.allow_call(now) {
+            execution
+                .yield_values
+                .push(serde_json::to_value(Response::circuit_open()).unwrap());
+            server.step_queue.push_back(execution);
+            return;
+        }
+    }
+
+    let span = execution.original_span.child();
+
+    events.send(SendMessageEvent {
+        sender: server_entity,
+        recipients: vec![recipient],
+        message: Message::Request(call.request.clone()),
+        span,
+    });
+
+    server.active_executions.insert(span.span_id, execution);
+    server.pending_calls.insert(span.span_id, call);
+}
+
+// Records the response to an upstream request with its circuit breaker, and schedules a retry if
+// the request failed and has retries left. Returns the execution if it should continue with the
+// response.
+#[allow(clippy::too_many_arguments)]
+fn finish_upstream_call(
+    server_entity: Entity,
+    server: &mut Server,
+    execution: ServerExecution,
+    span_id: Uuid,
+    response: &Response,
+    console_log: &mut ConsoleLog,
+    now: Duration,
+    jitter: f32,
+) -> Option<ServerExecution> {
+    let Some(call) = server.pending_calls.remove(&span_id) else {
+        return Some(execution);
+    };
+
+    let failed = is_failure(response);
+    let trace_id = execution.original_span.trace_id;
+
+    if let Some(config) = &call.options.circuit_breaker {
+        let breaker = server
+            .circuit_breakers
+            .entry(call.request.url.clone())
+            .or_default();
+
+        if breaker.record(failed, config, now) {
+            console_log.push(
+                server_entity,
+                now,
+                trace_id,
+                ConsoleMessage::warn(format!("Circuit breaker for {} opened.", call.request.url)),
+            );
+        }
+    }
+
+    if !failed || call.retry >= call.options.retries {
+        return Some(execution);
+    }
+
+    // An upstream asking for a longer wait is given it.
+    let backoff = call
+        .options
+        .backoff(call.retry, jitter)
+        .max(retry_after(response).unwrap_or_default());
+
+    console_log.push(
+        server_entity,
+        now,
+        trace_id,
+        ConsoleMessage::warn(format!(
+            "Request to {}{} failed with {}, retrying in {}ms.",
+            call.request.url,
+            call.request.path,
+            response.status,
+            backoff.as_millis()
+        )),
+    );
+
+    server.retries.push(PendingRetry {
+        ready_at: now.saturating_add(backoff),
+        execution,
+        call: UpstreamCall {
+            retry: call.retry + 1,
+            ..call
+        },
+    });
+
+    None
+}
+
 #[derive(Clone, Debug)]
 struct ServerExecution {
     request_handler: String,
+    middlewares: Vec<String>,
     request: Request,
     yield_values: Vec<Value>,
+    // The server state at the start of each step of the execution.
+    state_snapshots: Vec<Value>,
+    // The upstreams that were down at the start of each step, so replayed steps see the same
+    // health as when they first ran.
+    health_snapshots: Vec<Vec<String>>,
     original_sender: Entity,
-    original_trace_id: Uuid,
+    original_span: SpanContext,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Clone, Debug)]
 enum ExecutionError {
     NotFound,
+    MethodNotAllowed(Vec<HttpMethod>),
     BadRequest,
     InternalServerError(Value),
+    Uncaught { description: String, data: Value },
+    BudgetExceeded(String),
     ServiceUnavailable,
 }
 
-impl From<JsResult<Value>> for ExecutionError {
-    fn from(value: JsResult<Value>) -> Self {
-        match value {
-            Ok(value) => Self::InternalServerError(value),
-            Err(_) => Self::InternalServerError(Value::Null),
+impl ExecutionError {
+    // The message to show in the console log, for errors caused by the handler code.
+    fn log_message(&self) -> Option<String> {
+        match self {
+            ExecutionError::Uncaught { description, .. } => Some(format!("Uncaught {description}")),
+            ExecutionError::InternalServerError(value) => Some(value.to_string()),
+            ExecutionError::BudgetExceeded(reason) => {
+                Some(format!("Execution budget exceeded: {reason}"))
+            }
+            _ => None,
         }
     }
 }
 
+// Syntax errors include their line and column in the description, and other errors are given the
+// position of the latest statement to start running.
+fn uncaught_error(context: &mut Context<'_>, error: JsError) -> ExecutionError {
+    // Running into a runtime limit cannot be caught by the handler, and leaves the context unusable.
+    if error
+        .as_native()
+        .is_some_and(|native_error| native_error.is_runtime_limit())
+    {
+        return ExecutionError::BudgetExceeded(error.to_string());
+    }
+
+    let position = context
+        .global_object()
+        .get(POSITION_VARIABLE, context)
+        .ok()
+        .and_then(|position| position.as_string().map(|p| p.to_std_string_escaped()));
+
+    let description = match position {
+        Some(position) => format!("{error} at {position}"),
+        None => error.to_string(),
+    };
+
+    ExecutionError::Uncaught {
+        description,
+        data: error
+            .to_opaque(context)
+            .to_json(context)
+            .unwrap_or(Value::Null),
+    }
+}
+
 impl From<ExecutionError> for Response {
     fn from(value: ExecutionError) -> Self {
         match value {
             ExecutionError::NotFound => Response::not_found(),
+            ExecutionError::MethodNotAllowed(allowed_methods) => {
+                Response::method_not_allowed(&allowed_methods)
+            }
             ExecutionError::BadRequest => Response::bad_request(),
             ExecutionError::InternalServerError(value) => Response::internal_server_error(value),
+            ExecutionError::Uncaught { data, .. } => Response::internal_server_error(data),
+            ExecutionError::BudgetExceeded(_) => {
+                Response::internal_server_error("Execution budget exceeded.".into())
+            }
             ExecutionError::ServiceUnavailable => Response::service_unavailable(),
         }
     }
@@ -426,8 +1218,7 @@ impl From<ExecutionError> for Response {
 
 impl From<serde_json::Error> for ExecutionError {
     fn from(value: serde_json::Error) -> Self {
-        println!("{:?}", value);
-        println!("{:?}", value.to_string());
+        debug!("Incorrect yield value or response: {value}");
         // TODO: better message for client
         Self::InternalServerError(Value::from("Incorrect yield value or response."))
     }
@@ -436,37 +1227,134 @@ impl From<serde_json::Error> for ExecutionError {
 impl ServerExecution {
     fn new(
         request_handler: String,
+        middlewares: Vec<String>,
         request: Request,
         original_sender: Entity,
-        original_trace_id: Uuid,
+        original_span: SpanContext,
     ) -> Self {
         Self {
             request_handler,
+            middlewares,
             request,
             yield_values: vec![],
+            state_snapshots: vec![],
+            health_snapshots: vec![],
             original_sender,
-            original_trace_id,
+            original_span,
+        }
+    }
+
+    fn execute(&self) -> ExecutionStep {
+        let mut context = Context::default();
+
+        let runtime_limits = context.runtime_limits_mut();
+        runtime_limits.set_loop_iteration_limit(LOOP_ITERATION_LIMIT);
+        runtime_limits.set_recursion_limit(RECURSION_LIMIT);
+
+        let mut duration = Duration::ZERO;
+
+        let result = self
+            .execute_in(&mut context, &mut duration)
+            .and_then(|value| {
+                // Checked once the step has finished, as boa cannot interrupt a running script.
+                if duration > STEP_WALL_CLOCK_BUDGET {
+                    return Err(ExecutionError::BudgetExceeded(format!(
+                        "took longer than {}ms.",
+                        STEP_WALL_CLOCK_BUDGET.as_millis()
+                    )));
+                }
+
+                let latest_value = value
+                    .to_json(&mut context)
+                    .map_err(|error| uncaught_error(&mut context, error))?;
+
+                let state = context
+                    .global_object()
+                    .get("state", &mut context)
+                    .and_then(|state| state.to_json(&mut context))
+                    .map_err(|error| uncaught_error(&mut context, error))?;
+
+                // TODO: determine if the generator is done
+                // If it is, then create an ExecutionError indicating a wrong return type from the endpoint
+                // Otherwise malformed yield value
+
+                Ok((
+                    serde_json::from_value::<GeneratorResultValue>(latest_value)?,
+                    state,
+                ))
+            });
+
+        let console_output = context
+            .global_object()
+            .get("consoleOutput", &mut context)
+            .ok()
+            .and_then(|output| output.to_json(&mut context).ok())
+            .and_then(|output| serde_json::from_value(output).ok())
+            .unwrap_or_default();
+
+        let statements = context
+            .global_object()
+            .get(STATEMENT_COUNTER, &mut context)
+            .ok()
+            .and_then(|statements| statements.as_number())
+            .map_or(0, |statements| statements as usize);
+
+        ExecutionStep {
+            result,
+            console_output,
+            statements,
         }
     }
 
     // Because we cannot store Context in a Bevy Component (it is not Send + Sync), we instead create
     // a fresh Context and apply all the previous yield values to the generator in turn,
     // in order to get the latest yield value.
-    fn execute(&self) -> Result<GeneratorResultValue, ExecutionError> {
-        let mut context = Context::default();
+    // The server state is restored to its snapshot before each replayed step, so that a step's
+    // mutations are only applied to the server state once, when the step first runs.
+    // Returns the result of the latest step, and sets `duration` to the real time it took.
+    fn execute_in(
+        &self,
+        context: &mut Context<'_>,
+        duration: &mut Duration,
+    ) -> Result<JsValue, ExecutionError> {
+        let console_script = r#"
+var consoleOutput = [];
+const formatLogArg = (arg) => typeof arg === "string" ? arg : arg === undefined ? "undefined" : JSON.stringify(arg);
+const writeConsole = (level, args) => { consoleOutput.push({ level, message: args.map(formatLogArg).join(" ") }); };
+const console = {
+  log: (...args) => writeConsole("Log", args),
+  warn: (...args) => writeConsole("Warn", args),
+  error: (...args) => writeConsole("Error", args),
+};
+"#;
+
+        eval_setup(context, console_script)?;
 
         let request = serde_json::to_value(&self.request).unwrap();
-        let request = JsValue::from_json(&request, &mut context).unwrap();
+        let request = JsValue::from_json(&request, context).unwrap();
+
+        context
+            .register_global_property("request", request, Attribute::all())
+            .unwrap();
 
-        context.register_global_property("request", request, Attribute::all());
+        let state = JsValue::from_json(&Value::Object(Default::default()), context).unwrap();
+
+        // Not writable, so handlers can only mutate the shared object rather than replace it.
+        context
+            .register_global_property(
+                "state",
+                state,
+                Attribute::ENUMERABLE | Attribute::CONFIGURABLE,
+            )
+            .unwrap();
 
         let http_script = r#"
 const http = {
-  get: function(url, path) { return { Request: { url, path, method: "Get", body: null, params: {} }}; },
+  get: function(url, path, options) { return { Request: { url, path, method: "Get", body: null, params: {}, headers: (options && options.headers) || {}, options: options || null }}; },
 }
         "#;
 
-        context.eval(http_script).unwrap();
+        eval_setup(context, http_script)?;
 
         let db_script = r#"
 const db = {
@@ -478,71 +1366,218 @@ const db = {
 };
           "#;
 
-        context.eval(db_script).unwrap();
+        eval_setup(context, db_script)?;
 
         let response_script = r#"
-function response(status, data) {
-  return { Response: { status, data } };
+function response(status, data, headers) {
+  return { Response: { status, data, headers: headers || {} } };
 }
+function sleep(ms) {
+  return { Sleep: Math.max(0, ms) };
+}
+          "#;
+
+        eval_setup(context, response_script)?;
+
+        let health_script = r#"
+const health = {
+  isUp: function(hostname) { return !downHostnames.includes(hostname); },
+  up: function(hostnames) { return hostnames.filter((hostname) => health.isUp(hostname)); },
+};
           "#;
 
-        context.eval(response_script).unwrap();
+        eval_setup(context, health_script)?;
+
+        eval_setup(
+            context,
+            &format!("var {POSITION_VARIABLE}; var {STATEMENT_COUNTER} = 0;"),
+        )?;
+
+        let request_handler = mark_script(context, &self.request_handler, |line| {
+            format!("line {line}")
+        });
+
+        clear_position(context);
 
         context
-            .eval(&self.request_handler)
-            .map_err(|error| error.to_json(&mut context))?;
+            .eval(Source::from_bytes(&request_handler))
+            .map_err(|error| uncaught_error(context, error))?;
+
+        // Each middleware is evaluated in its own scope, so they can all declare `middleware`.
+        let middleware_setup = format!(
+            "const middlewares = [{}];",
+            self.middlewares
+                .iter()
+                .enumerate()
+                .map(|(idx, m)| {
+                    let m = mark_script(context, m, |line| {
+                        format!("line {line} of middleware #{}", idx + 1)
+                    });
+                    format!("(function () {{\n{m}\nreturn middleware;\n}})()")
+                })
+                .collect::<Vec<_>>()
+                .join(",\n")
+        );
+
+        clear_position(context);
+
+        context
+            .eval(Source::from_bytes(&middleware_setup))
+            .map_err(|error| uncaught_error(context, error))?;
 
         let generator_setup = r#"
-const gen = requestHandler(request);
+const runMiddleware = function* (idx) {
+  if (idx < middlewares.length) {
+    return yield* middlewares[idx](request, function* () { return yield* runMiddleware(idx + 1); });
+  }
+  return yield* requestHandler(request);
+};
+const gen = runMiddleware(0);
 "#;
 
-        context.eval(generator_setup).unwrap();
+        eval_setup(context, generator_setup)?;
+
+        let restore_state_script = r#"
+function restoreState(snapshot) {
+  for (const key of Object.keys(state)) {
+    delete state[key];
+  }
+  Object.assign(state, snapshot);
+}
+"#;
+
+        eval_setup(context, restore_state_script)?;
+
+        let mut state_snapshots = self.state_snapshots.iter();
+        let mut health_snapshots = self.health_snapshots.iter();
 
-        let mut value = context
-            .eval(
+        restore_state(context, state_snapshots.next().unwrap())?;
+        restore_health(context, health_snapshots.next().unwrap());
+
+        clear_position(context);
+
+        let step_start = Instant::now();
+
+        let value = context
+            .eval(Source::from_bytes(&format!(
                 r#"
+consoleOutput.length = 0;
+{STATEMENT_COUNTER} = 0;
 gen.next();
-"#,
-            )
-            .map_err(|error| error.to_json(&mut context))?;
+"#
+            )))
+            .map_err(|error| uncaught_error(context, error));
 
-        for prev_yield_value in self.yield_values.iter() {
-            let prev_js_yield_value = JsValue::from_json(prev_yield_value, &mut context).unwrap();
+        *duration = step_start.elapsed();
 
-            context.register_global_property(
-                "lastGenResult",
-                prev_js_yield_value,
-                Attribute::all(),
-            );
+        let mut value = value?;
+
+        for (prev_yield_value, (state_snapshot, health_snapshot)) in self
+            .yield_values
+            .iter()
+            .zip(state_snapshots.zip(health_snapshots))
+        {
+            restore_state(context, state_snapshot)?;
+            restore_health(context, health_snapshot);
 
-            value = context
-                .eval(
+            let prev_js_yield_value = JsValue::from_json(prev_yield_value, context).unwrap();
+
+            context
+                .register_global_property("lastGenResult", prev_js_yield_value, Attribute::all())
+                .unwrap();
+
+            clear_position(context);
+
+            let step_start = Instant::now();
+
+            let next_value = context
+                .eval(Source::from_bytes(&format!(
                     r#"
+consoleOutput.length = 0;
+{STATEMENT_COUNTER} = 0;
 gen.next(lastGenResult);
-"#,
-                )
-                .map_err(|error| error.to_json(&mut context))?;
+"#
+                )))
+                .map_err(|error| uncaught_error(context, error));
+
+            *duration = step_start.elapsed();
+
+            value = next_value?;
         }
 
-        let latest_value = value
-            .to_json(&mut context)
-            .map_err(|error| error.to_json(&mut context))?;
+        Ok(value)
+    }
+}
 
-        println!("LATEST YIELD JS VALUE:");
-        println!("{:?}", latest_value);
+fn is_script_valid(script: &str) -> bool {
+    let mut context = Context::default();
 
-        // TODO: determine if the generator is done
-        // If it is, then create an ExecutionError indicating a wrong return type from the endpoint
-        // Otherwise malformed yield value
+    Script::parse(Source::from_bytes(script), None, &mut context).is_ok()
+}
 
-        match serde_json::from_value::<GeneratorResultValue>(latest_value) {
-            Ok(v) => Ok(v),
-            Err(e) => Err(e.into()),
-        }
+// Marks the script with the position of its statements, unless that would stop it from parsing.
+fn mark_script(
+    context: &mut Context<'_>,
+    script: &str,
+    position: impl Fn(usize) -> String,
+) -> String {
+    let marked = mark_lines(script, position);
+
+    if Script::parse(Source::from_bytes(&marked), None, context).is_ok() {
+        marked
+    } else {
+        script.to_string()
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+fn clear_position(context: &mut Context<'_>) {
+    context
+        .global_object()
+        .set(POSITION_VARIABLE, JsValue::undefined(), false, context)
+        .unwrap();
+}
+
+fn eval_setup(context: &mut Context<'_>, script: &str) -> Result<(), ExecutionError> {
+    context
+        .eval(Source::from_bytes(script))
+        .map_err(|error| uncaught_error(context, error))?;
+
+    Ok(())
+}
+
+fn restore_state(context: &mut Context<'_>, snapshot: &Value) -> Result<(), ExecutionError> {
+    let snapshot = JsValue::from_json(snapshot, context).unwrap();
+
+    context
+        .register_global_property("stateSnapshot", snapshot, Attribute::all())
+        .unwrap();
+
+    context
+        .eval(Source::from_bytes("restoreState(stateSnapshot);"))
+        .map_err(|error| uncaught_error(context, error))?;
+
+    Ok(())
+}
+
+fn restore_health(context: &mut Context<'_>, down_hostnames: &[String]) {
+    let down_hostnames = JsValue::from_json(&Value::from(down_hostnames), context).unwrap();
+
+    context
+        .register_global_property("downHostnames", down_hostnames, Attribute::all())
+        .unwrap();
+}
+
+// The outcome of running the latest step of an execution.
+struct ExecutionStep {
+    // The latest yield value, along with the server state after the latest step.
+    result: Result<(GeneratorResultValue, Value), ExecutionError>,
+    // The console output written during the latest step.
+    console_output: Vec<ConsoleMessage>,
+    // The statements that started a line of the handler or middlewares during the latest step.
+    statements: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct GeneratorResultValue {
     pub done: bool,
     pub value: YieldValue,
@@ -554,149 +1589,542 @@ pub enum YieldValue {
     Request(Request),
     DatabaseCall(DatabaseCall),
     DatabaseAnswer(Value),
+    // Milliseconds of simulated processing time.
+    Sleep(f64),
 }
 
-#[derive(PartialEq, Eq, Debug)]
-struct EndpointMatch<'a> {
-    path: &'a String,
-    params: HashMap<String, String>,
-}
+#[cfg(test)]
+mod test {
+    use bevy::prelude::{App, Events};
 
-fn map_url_to_path_with_params<'a>(
-    url: &str,
-    endpoints_paths: Vec<&'a String>,
-) -> Option<EndpointMatch<'a>> {
-    let url_segments: Vec<_> = url.split('/').collect();
-    for endpoint_path in endpoints_paths.iter() {
-        let endpoint_segments: Vec<_> = endpoint_path.split('/').collect();
+    use crate::{console::LogLevel, node::Hostname};
 
-        if url_segments.len() != endpoint_segments.len() {
-            continue;
-        }
+    use super::super::NodeConnections;
 
-        let mut params: HashMap<String, String> = HashMap::new();
-
-        for (idx, (url_segment, endpoint_segment)) in url_segments
-            .iter()
-            .zip(endpoint_segments.iter())
-            .enumerate()
-        {
-            if endpoint_segment.starts_with(':') {
-                params.insert(
-                    endpoint_segment.strip_prefix(':').unwrap().to_string(),
-                    url_segment.to_string(),
-                );
-            } else if url_segment != endpoint_segment {
-                break;
-            }
+    use super::*;
 
-            if idx == url_segments.len() - 1 {
-                return Some(EndpointMatch {
-                    path: endpoint_path,
-                    params,
-                });
-            }
-        }
+    fn execute(request_handler: &str) -> Result<GeneratorResultValue, ExecutionError> {
+        execute_with_middlewares(request_handler, &[])
     }
-    None
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    fn execute_with_middlewares(
+        request_handler: &str,
+        middlewares: &[&str],
+    ) -> Result<GeneratorResultValue, ExecutionError> {
+        let mut execution = ServerExecution::new(
+            request_handler.to_string(),
+            middlewares.iter().map(|m| m.to_string()).collect(),
+            Request::default(),
+            Entity::from_raw(0),
+            SpanContext::root(Uuid::nil()),
+        );
+
+        execution
+            .state_snapshots
+            .push(Value::Object(Default::default()));
+        execution.health_snapshots.push(vec![]);
+
+        execution.execute().result.map(|(res, _)| res)
+    }
 
     #[test]
-    fn should_return_matching_path_and_empty_params_for_root() {
-        let url: &str = "/";
-        let endpoints_paths = ["/".to_string()];
+    fn should_return_response_within_budget() {
+        let res = execute(EXAMPLE_REQUEST_HANDLER).unwrap();
+
+        assert_eq!(
+            YieldValue::Response(Response {
+                status: 200,
+                data: "Ok".into(),
+                ..Default::default()
+            }),
+            res.value
+        );
+    }
 
-        let endpoints_paths: Vec<&String> = endpoints_paths.iter().collect();
+    #[test]
+    fn should_respond_from_middleware_without_calling_handler() {
+        let res = execute_with_middlewares(
+            r#"const requestHandler = function* () {
+  throw new Error("The handler should not run.");
+}"#,
+            &[r#"const middleware = function* (request, next) {
+  return response(401, "Unauthorized");
+}"#],
+        )
+        .unwrap();
 
+        assert!(res.done);
         assert_eq!(
-            Some(EndpointMatch {
-                path: &"/".to_string(),
-                params: HashMap::new()
+            YieldValue::Response(Response {
+                status: 401,
+                data: "Unauthorized".into(),
+                ..Default::default()
             }),
-            map_url_to_path_with_params(url, endpoints_paths)
+            res.value
+        );
+    }
+
+    #[test]
+    fn should_pass_request_changed_by_middlewares_to_handler() {
+        let res = execute_with_middlewares(
+            r#"const requestHandler = function* (request) {
+  return response(200, request.user);
+}"#,
+            &[
+                r#"const middleware = function* (request, next) {
+  request.user = "alice";
+  return yield* next();
+}"#,
+                r#"const middleware = function* (request, next) {
+  request.user = request.user.toUpperCase();
+  return yield* next();
+}"#,
+            ],
         )
+        .unwrap();
+
+        assert_eq!(
+            YieldValue::Response(Response {
+                status: 200,
+                data: "ALICE".into(),
+                ..Default::default()
+            }),
+            res.value
+        );
+    }
+
+    fn execution_with_state(request_handler: &str, state: Value) -> ServerExecution {
+        let mut execution = ServerExecution::new(
+            request_handler.to_string(),
+            vec![],
+            Request::default(),
+            Entity::from_raw(0),
+            SpanContext::root(Uuid::nil()),
+        );
+
+        execution.state_snapshots.push(state);
+        execution.health_snapshots.push(vec![]);
+
+        execution
     }
 
     #[test]
-    fn should_return_matching_path_with_single_param_after_root() {
-        let url: &str = "/1";
-        let endpoints_paths = ["/:id".to_string()];
+    fn should_persist_state_between_requests() {
+        let request_handler = r#"const requestHandler = function* () {
+  state.count = (state.count || 0) + 1;
+  return response(200, state.count);
+}"#;
+
+        let (_, state) = execution_with_state(request_handler, Value::Object(Default::default()))
+            .execute()
+            .result
+            .unwrap();
 
-        let endpoints_paths: Vec<&String> = endpoints_paths.iter().collect();
+        assert_eq!(serde_json::json!({ "count": 1 }), state);
 
+        let (res, state) = execution_with_state(request_handler, state)
+            .execute()
+            .result
+            .unwrap();
+
+        assert_eq!(serde_json::json!({ "count": 2 }), state);
         assert_eq!(
-            Some(EndpointMatch {
-                path: &"/:id".to_string(),
-                params: HashMap::from_iter([("id".to_string(), "1".to_string())])
+            YieldValue::Response(Response {
+                status: 200,
+                data: 2.into(),
+                ..Default::default()
             }),
-            map_url_to_path_with_params(url, endpoints_paths)
-        )
+            res.value
+        );
     }
 
     #[test]
-    fn should_return_matching_path_with_single_param() {
-        let url: &str = "/orders/1";
-        let endpoints_paths = [
-            "/orders".to_string(),
-            "/orders/:id".to_string(),
-            "/orders/:id/items".to_string(),
-            "/users".to_string(),
-            "/users/:id".to_string(),
-            "/users/:id/messages".to_string(),
-        ];
-
-        let endpoints_paths: Vec<&String> = endpoints_paths.iter().collect();
+    fn should_apply_state_mutations_of_replayed_steps_once() {
+        let mut execution = execution_with_state(
+            r#"const requestHandler = function* () {
+  state.count = (state.count || 0) + 1;
+  yield sleep(10);
+  state.count += 10;
+  return response(200, state.count);
+}"#,
+            Value::Object(Default::default()),
+        );
 
+        let (_, state) = execution.execute().result.unwrap();
+
+        assert_eq!(serde_json::json!({ "count": 1 }), state);
+
+        // The first step is replayed from its own snapshot, then the second step continues from
+        // the state the first step left behind.
+        execution.yield_values.push(Value::Null);
+        execution.state_snapshots.push(state);
+        execution.health_snapshots.push(vec![]);
+
+        let (res, state) = execution.execute().result.unwrap();
+
+        assert_eq!(serde_json::json!({ "count": 11 }), state);
         assert_eq!(
-            Some(EndpointMatch {
-                path: &"/orders/:id".to_string(),
-                params: HashMap::from_iter([("id".to_string(), "1".to_string())])
+            YieldValue::Response(Response {
+                status: 200,
+                data: 11.into(),
+                ..Default::default()
             }),
-            map_url_to_path_with_params(url, endpoints_paths)
+            res.value
+        );
+    }
+
+    #[test]
+    fn should_capture_console_output_of_the_latest_step() {
+        let mut execution = execution_with_state(
+            r#"const requestHandler = function* () {
+  console.log("first step");
+  yield sleep(10);
+  console.log("user", { id: 1 });
+  console.warn("slow", 250);
+  console.error(undefined);
+  return response(200, "Ok");
+}"#,
+            Value::Object(Default::default()),
+        );
+
+        execution.yield_values.push(Value::Null);
+        execution
+            .state_snapshots
+            .push(Value::Object(Default::default()));
+        execution.health_snapshots.push(vec![]);
+
+        assert_eq!(
+            vec![
+                ConsoleMessage {
+                    level: LogLevel::Log,
+                    message: r#"user {"id":1}"#.to_string(),
+                },
+                ConsoleMessage::warn("slow 250".to_string()),
+                ConsoleMessage::error("undefined".to_string()),
+            ],
+            execution.execute().console_output
+        );
+    }
+
+    #[test]
+    fn should_report_line_of_runtime_errors() {
+        let res = execute(
+            r#"const requestHandler = function* (request) {
+  const user = { name: "alice" };
+
+  return response(200, user.address.street);
+}"#,
+        );
+
+        let Err(ExecutionError::Uncaught { description, .. }) = res else {
+            panic!("Expected an uncaught error, got {res:?}");
+        };
+
+        assert!(description.starts_with("TypeError"), "{description}");
+        assert!(description.ends_with(" at line 4"), "{description}");
+
+        let res = execute_with_middlewares(
+            EXAMPLE_REQUEST_HANDLER,
+            &[r#"const middleware = function* (request, next) {
+  throw new Error("Unauthorized");
+}"#],
+        );
+
+        let Err(ExecutionError::Uncaught { description, .. }) = res else {
+            panic!("Expected an uncaught error, got {res:?}");
+        };
+
+        assert_eq!(
+            "Error: Unauthorized at line 2 of middleware #1",
+            description
+        );
+    }
+
+    #[test]
+    fn should_yield_sleep_for_non_negative_milliseconds() {
+        let res = execute(
+            r#"const requestHandler = function* () {
+  yield sleep(-5);
+  return response(200, "Ok");
+}"#,
         )
+        .unwrap();
+
+        assert_eq!(YieldValue::Sleep(0.0), res.value);
     }
 
     #[test]
-    fn should_return_matching_path_with_multiple_params() {
-        let url: &str = "/orders/123/items/456";
-        let endpoints_paths = [
-            "/orders".to_string(),
-            "/orders/:orderId".to_string(),
-            "/orders/:orderId/items".to_string(),
-            "/orders/:orderId/items/:itemId".to_string(),
-            "/orders/:orderId/items/:itemId/id".to_string(),
-        ];
-
-        let endpoints_paths: Vec<&String> = endpoints_paths.iter().collect();
+    fn should_add_base_cost_to_first_step_only() {
+        let server = Server::default()
+            .with_processing_costs(Duration::from_millis(10), Duration::from_millis(5));
+
+        let mut execution = ServerExecution::new(
+            String::new(),
+            vec![],
+            Request::default(),
+            Entity::from_raw(0),
+            SpanContext::root(Uuid::nil()),
+        );
+
+        let sleep_step = ExecutionStep {
+            result: Ok((
+                GeneratorResultValue {
+                    done: false,
+                    value: YieldValue::Sleep(20.0),
+                },
+                Value::Null,
+            )),
+            console_output: vec![],
+            statements: 0,
+        };
+
+        assert_eq!(
+            Duration::from_millis(35),
+            server.processing_time(&execution, &sleep_step)
+        );
+
+        execution.yield_values.push(Value::Null);
 
         assert_eq!(
-            Some(EndpointMatch {
-                path: &"/orders/:orderId/items/:itemId".to_string(),
-                params: HashMap::from_iter([
-                    ("orderId".to_string(), "123".to_string()),
-                    ("itemId".to_string(), "456".to_string())
-                ])
+            Duration::from_millis(25),
+            server.processing_time(&execution, &sleep_step)
+        );
+    }
+
+    #[test]
+    fn should_count_the_same_statements_each_time_a_step_runs() {
+        let mut execution = execution_with_state(
+            r#"const requestHandler = function* () {
+  yield sleep(10);
+  let total = 0;
+  for (let i = 0; i < 1000; i++) {
+    total += i;
+  }
+  return response(200, total);
+}"#,
+            Value::Object(Default::default()),
+        );
+
+        let first_step = execution.execute();
+
+        assert_eq!(1, first_step.statements);
+        assert_eq!(1, execution.execute().statements);
+
+        execution.yield_values.push(Value::Null);
+        execution
+            .state_snapshots
+            .push(Value::Object(Default::default()));
+        execution.health_snapshots.push(vec![]);
+
+        // Only the statements of the latest step count, and not those of replayed steps.
+        let second_step = execution.execute();
+
+        assert!(second_step.result.is_ok());
+        assert_eq!(1003, second_step.statements);
+        assert_eq!(1003, execution.execute().statements);
+    }
+
+    #[test]
+    fn should_charge_cpu_cost_per_thousand_statements() {
+        let server = Server::default().with_cpu_cost(Some(2.0));
+
+        let execution = ServerExecution::new(
+            String::new(),
+            vec![],
+            Request::default(),
+            Entity::from_raw(0),
+            SpanContext::root(Uuid::nil()),
+        );
+
+        let step = ExecutionStep {
+            result: Err(ExecutionError::BadRequest),
+            console_output: vec![],
+            statements: 5000,
+        };
+
+        assert_eq!(
+            Duration::from_millis(10),
+            server.processing_time(&execution, &step)
+        );
+
+        // Costs that cannot be a duration add nothing, rather than panic.
+        for cpu_cost in [-1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                Duration::ZERO,
+                Server::default()
+                    .with_cpu_cost(Some(cpu_cost))
+                    .processing_time(&execution, &step)
+            );
+        }
+    }
+
+    #[test]
+    fn should_clamp_sleep_time() {
+        let server = Server::default();
+
+        let execution = ServerExecution::new(
+            String::new(),
+            vec![],
+            Request::default(),
+            Entity::from_raw(0),
+            SpanContext::root(Uuid::nil()),
+        );
+
+        let sleep_time = |ms| {
+            let step = ExecutionStep {
+                result: Ok((
+                    GeneratorResultValue {
+                        done: false,
+                        value: YieldValue::Sleep(ms),
+                    },
+                    Value::Null,
+                )),
+                console_output: vec![],
+                statements: 0,
+            };
+
+            server.processing_time(&execution, &step)
+        };
+
+        assert_eq!(Duration::from_millis(250), sleep_time(250.0));
+        assert_eq!(MAX_SLEEP, sleep_time(1e30));
+        assert_eq!(Duration::ZERO, sleep_time(-5.0));
+        assert_eq!(Duration::ZERO, sleep_time(f64::NAN));
+    }
+
+    #[test]
+    fn should_read_health_of_upstreams_at_the_start_of_the_step() {
+        let mut execution = ServerExecution::new(
+            r#"const requestHandler = function* () {
+  const before = health.up(["server1", "server2"]);
+  yield sleep(10);
+  return response(200, { before, after: health.isUp("server2") });
+}"#
+            .to_string(),
+            vec![],
+            Request::default(),
+            Entity::from_raw(0),
+            SpanContext::root(Uuid::nil()),
+        );
+
+        for down_hostnames in [vec!["server1".to_string()], vec![]] {
+            execution
+                .state_snapshots
+                .push(Value::Object(Default::default()));
+            execution.health_snapshots.push(down_hostnames);
+        }
+
+        execution.yield_values.push(Value::Null);
+
+        let (res, _) = execution.execute().result.unwrap();
+
+        assert_eq!(
+            YieldValue::Response(Response {
+                status: 200,
+                data: serde_json::json!({ "before": ["server2"], "after": true }),
+                ..Default::default()
             }),
-            map_url_to_path_with_params(url, endpoints_paths)
-        )
+            res.value
+        );
     }
 
     #[test]
-    fn should_return_none_for_no_matching_path() {
-        let url: &str = "/users/123";
-        let endpoints_paths = [
-            "/users".to_string(),
-            "/users/:userId/messages".to_string(),
-            "/orders/:orderId/items".to_string(),
-            "/orders/:orderId/items/:itemId".to_string(),
-        ];
-
-        let endpoints_paths: Vec<&String> = endpoints_paths.iter().collect();
-
-        assert_eq!(None, map_url_to_path_with_params(url, endpoints_paths))
+    fn should_mark_upstreams_down_that_fail_health_checks() {
+        let mut server = Server::default();
+        server.health_checks.hostnames = vec!["server1".to_string(), "server2".to_string()];
+
+        let [span1, span2, span3] = [1, 2, 3].map(Uuid::from_u128);
+
+        assert_eq!(
+            vec!["server1", "server2"],
+            server.due_health_checks(Duration::ZERO)
+        );
+
+        server.start_health_check(span1, "server1".to_string());
+        server.start_health_check(span2, "server2".to_string());
+
+        assert!(server.finish_health_check(span1, &Response::healthy()));
+        assert!(!server.finish_health_check(span3, &Response::healthy()));
+        assert!(server
+            .due_health_checks(Duration::from_millis(999))
+            .is_empty());
+
+        // server2 never answered before the next round.
+        assert_eq!(2, server.due_health_checks(Duration::from_secs(1)).len());
+        assert!(server.is_upstream_up("server1"));
+        assert!(!server.is_upstream_up("server2"));
+
+        server.start_health_check(span3, "server1".to_string());
+        server.finish_health_check(span3, &Response::service_unavailable());
+
+        assert!(!server.is_upstream_up("server1"));
+    }
+
+    #[test]
+    fn should_stop_health_checks_once_every_client_has_finished() {
+        let mut app = App::new();
+        app.add_event::<SendMessageEvent>();
+        app.init_resource::<ConsoleLog>();
+        app.init_resource::<SimulationClock>();
+        app.init_resource::<SimulationRng>();
+        app.add_system(server_system);
+
+        let upstream = app
+            .world
+            .spawn((Hostname("primary.com".to_string()), NodeConnections::new()))
+            .id();
+
+        let mut server = Server::default();
+        server.health_checks.hostnames = vec!["primary.com".to_string()];
+        server.start_simulation();
+
+        let mut connections = NodeConnections::new();
+        connections.add_connection(upstream, Entity::from_raw(100));
+        app.world.spawn((server, connections));
+
+        let mut client = Client::new();
+        client.state = ClientState::Finished;
+        let client = app.world.spawn((client, NodeConnections::new())).id();
+
+        let num_health_checks = |app: &mut App| {
+            app.update();
+            app.world
+                .resource_mut::<Events<SendMessageEvent>>()
+                .drain()
+                .filter(|event| matches!(event.message, Message::HealthCheck))
+                .count()
+        };
+
+        assert_eq!(0, num_health_checks(&mut app));
+
+        app.world.get_mut::<Client>(client).unwrap().state = ClientState::SendNextRequest;
+
+        assert_eq!(1, num_health_checks(&mut app));
+    }
+
+    #[test]
+    fn should_exceed_budget_for_infinite_loop() {
+        let res = execute(
+            r#"const requestHandler = function* () {
+  try {
+    while (true) {}
+  } catch (e) {}
+  return response(200, "Ok");
+}"#,
+        );
+
+        assert!(matches!(res, Err(ExecutionError::BudgetExceeded(_))));
+    }
+
+    #[test]
+    fn should_exceed_budget_for_unbounded_recursion() {
+        let res = execute(
+            r#"const requestHandler = function* () {
+  const recurse = () => recurse();
+  recurse();
+}"#,
+        );
+
+        assert!(matches!(res, Err(ExecutionError::BudgetExceeded(_))));
     }
 }
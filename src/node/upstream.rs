@@ -0,0 +1,190 @@
+use std::{fmt::Display, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::message::Response;
+
+/// How a Server makes a call to an upstream, given as the last argument of `http.get`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CallOptions {
+    // How many more times a failed call is sent before its failure is returned to the handler.
+    pub retries: u32,
+    // The delay before the first retry, which doubles for every retry after it.
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+impl Default for CallOptions {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            backoff_ms: 100,
+            max_backoff_ms: 5000,
+            circuit_breaker: None,
+        }
+    }
+}
+
+impl CallOptions {
+    /// The delay before the given retry, counting from 0. Half of the delay is jittered by
+    /// `jitter`, between 0 and 1, so that callers retrying at once spread out.
+    pub fn backoff(&self, retry: u32, jitter: f32) -> Duration {
+        let backoff_ms = self
+            .backoff_ms
+            .saturating_mul(2u64.saturating_pow(retry))
+            .min(self.max_backoff_ms);
+
+        let jitter_ms = (backoff_ms as f32 / 2.0 * jitter.clamp(0.0, 1.0)).round() as u64;
+
+        Duration::from_millis(backoff_ms / 2 + jitter_ms)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CircuitBreakerConfig {
+    // The number of failures in a row that opens the breaker.
+    pub failure_threshold: u32,
+    // How long the breaker stays open before letting a trial call through.
+    pub reset_ms: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            reset_ms: 5000,
+        }
+    }
+}
+
+/// How long the upstream asked to wait before the next request, from its `Retry-After` header.
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers
+        .get("Retry-After")
+        .and_then(|secs| secs.parse().ok())
+        .map(Duration::from_secs)
+}
+
+/// Whether an upstream call failed in a way worth retrying, and counts against its breaker.
+pub fn is_failure(response: &Response) -> bool {
+    response.status >= 500 || response.status == 429
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CircuitState {
+    #[default]
+    Closed,
+    // Calls fail straight away, without reaching the upstream.
+    Open,
+    // A single trial call has been let through, and decides whether the breaker closes again.
+    HalfOpen,
+}
+
+impl Display for CircuitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "Closed"),
+            CircuitState::Open => write!(f, "Open"),
+            CircuitState::HalfOpen => write!(f, "Half-open"),
+        }
+    }
+}
+
+/// Stops a Server from calling an upstream that keeps failing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    pub state: CircuitState,
+    // Failures in a row while closed.
+    pub failures: u32,
+    open_until: Duration,
+}
+
+impl CircuitBreaker {
+    /// Whether a call may be sent now. Once an open breaker's reset time has passed, it lets one
+    /// trial call through.
+    pub fn allow_call(&mut self, now: Duration) -> bool {
+        match self.state {
+            CircuitState::Closed => true,
+            CircuitState::Open if now >= self.open_until => {
+                self.state = CircuitState::HalfOpen;
+                true
+            }
+            CircuitState::Open | CircuitState::HalfOpen => false,
+        }
+    }
+
+    /// Records the outcome of a call, returning whether it opened the breaker.
+    pub fn record(&mut self, failed: bool, config: &CircuitBreakerConfig, now: Duration) -> bool {
+        if !failed {
+            *self = Self::default();
+            return false;
+        }
+
+        let should_open = match self.state {
+            CircuitState::Closed => {
+                self.failures += 1;
+                self.failures >= config.failure_threshold
+            }
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+
+        if should_open {
+            self.state = CircuitState::Open;
+            self.failures = 0;
+            self.open_until = now + Duration::from_millis(config.reset_ms);
+        }
+
+        should_open
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_double_backoff_up_to_the_maximum() {
+        let options = CallOptions {
+            retries: 5,
+            backoff_ms: 100,
+            max_backoff_ms: 300,
+            circuit_breaker: None,
+        };
+
+        assert_eq!(Duration::from_millis(50), options.backoff(0, 0.0));
+        assert_eq!(Duration::from_millis(100), options.backoff(0, 1.0));
+        assert_eq!(Duration::from_millis(150), options.backoff(1, 0.5));
+        assert_eq!(Duration::from_millis(300), options.backoff(4, 1.0));
+    }
+
+    #[test]
+    fn should_open_after_failures_and_close_after_a_successful_trial() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 2,
+            reset_ms: 1000,
+        };
+        let mut breaker = CircuitBreaker::default();
+        let at = Duration::from_millis;
+
+        assert!(!breaker.record(true, &config, at(0)));
+        assert!(breaker.record(true, &config, at(100)));
+        assert_eq!(CircuitState::Open, breaker.state);
+        assert!(!breaker.allow_call(at(1099)));
+
+        assert!(breaker.allow_call(at(1100)));
+        assert_eq!(CircuitState::HalfOpen, breaker.state);
+        assert!(!breaker.allow_call(at(1100)));
+
+        // A failed trial opens the breaker again straight away.
+        assert!(breaker.record(true, &config, at(1200)));
+        assert!(breaker.allow_call(at(2200)));
+
+        breaker.record(false, &config, at(2300));
+        assert_eq!(CircuitBreaker::default(), breaker);
+    }
+}
@@ -0,0 +1,357 @@
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_state::{AppState, GameMode},
+    level::{CurrentLevel, Level, LevelState},
+    node::{
+        client::{Client, RequestConfig},
+        rate_limit::RateLimit,
+        server::{Endpoint, HealthChecks, Middleware, Server},
+        Hostname, NodeConnections, NodeName, NodeType, SystemNodeTrait,
+    },
+    score::{score_solution, ScoreBreakdown},
+};
+
+pub struct ProgressPlugin;
+
+impl Plugin for ProgressPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Progress::load());
+
+        app.add_system(
+            save_design
+                .run_if(in_state(GameMode::Levels))
+                .in_schedule(OnExit(AppState::Edit)),
+        );
+        app.add_system(
+            record_level_result
+                .after(score_solution)
+                .run_if(in_state(GameMode::Levels))
+                .in_schedule(OnEnter(AppState::SimulateFinish)),
+        );
+    }
+}
+
+/// The player's progress through the levels, keyed by level id, saved under the user's data
+/// directory.
+#[derive(Resource, Serialize, Deserialize, Default, Debug)]
+pub struct Progress {
+    #[serde(default)]
+    levels: HashMap<String, LevelProgress>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct LevelProgress {
+    pub completed: bool,
+    // The lowest score of a passing solution, and the most stars earned by one.
+    pub best_score: Option<u32>,
+    pub best_stars: Option<u8>,
+    // The design the level was last left with.
+    pub design: Option<Design>,
+}
+
+impl Progress {
+    fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("system-designer").join("progress.json"))
+    }
+
+    fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+
+        let mut progress: Self = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+                error!("Failed to read progress from {}: {error}", path.display());
+                Self::default()
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(error) => {
+                error!("Failed to read progress from {}: {error}", path.display());
+                Self::default()
+            }
+        };
+
+        // The file may have been edited by hand, and no solution earns more than 3 stars.
+        for level in progress.levels.values_mut() {
+            level.best_stars = level.best_stars.map(|stars| stars.min(3));
+        }
+
+        progress
+    }
+
+    fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| Ok(serde_json::to_string_pretty(self)?))
+            .and_then(|contents| fs::write(&path, contents));
+
+        if let Err(error) = result {
+            error!("Failed to save progress to {}: {error}", path.display());
+        }
+    }
+
+    pub fn level(&self, level_id: &str) -> Option<&LevelProgress> {
+        self.levels.get(level_id)
+    }
+
+    pub fn design(&self, level_id: &str) -> Option<&Design> {
+        self.level(level_id)
+            .and_then(|progress| progress.design.as_ref())
+    }
+
+    pub fn clear_design(&mut self, level_id: &str) {
+        if let Some(progress) = self.levels.get_mut(level_id) {
+            progress.design = None;
+            self.save();
+        }
+    }
+
+    pub fn is_completed(&self, level_id: &str) -> bool {
+        self.levels
+            .get(level_id)
+            .is_some_and(|progress| progress.completed)
+    }
+
+    /// Levels are unlocked once all of their prerequisites are completed.
+    pub fn is_unlocked(&self, level: &Level) -> bool {
+        level
+            .prerequisites
+            .iter()
+            .all(|prerequisite| self.is_completed(prerequisite))
+    }
+
+    fn record_result(&mut self, level_id: &str, passed: bool, score: Option<&ScoreBreakdown>) {
+        if !passed {
+            return;
+        }
+
+        let progress = self.levels.entry(level_id.to_string()).or_default();
+        progress.completed = true;
+
+        if let Some(score) = score {
+            let points = score.score();
+            progress.best_score = Some(
+                progress
+                    .best_score
+                    .map_or(points, |best_score| best_score.min(points)),
+            );
+            progress.best_stars = progress.best_stars.max(score.stars);
+        }
+    }
+}
+
+/// The nodes and connections of a system, so it can be rebuilt when a level is revisited.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Design {
+    pub nodes: Vec<DesignNode>,
+    // Indices into `nodes` of the two ends of every connection.
+    pub connections: Vec<(usize, usize)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DesignNode {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    pub component: DesignComponent,
+}
+
+impl DesignNode {
+    pub fn node_type(&self) -> NodeType {
+        match self.component {
+            DesignComponent::Client { .. } => NodeType::Client,
+            DesignComponent::Server { .. } => NodeType::Server,
+            DesignComponent::Database { .. } => NodeType::Database,
+        }
+    }
+}
+
+/// What the player can change about each type of node.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum DesignComponent {
+    Client {
+        request_configs: Vec<RequestConfig>,
+    },
+    Server {
+        hostname: String,
+        endpoints: Vec<Endpoint>,
+        middlewares: Vec<Middleware>,
+        #[serde(default)]
+        health_checks: HealthChecks,
+        #[serde(default)]
+        rate_limit: Option<RateLimit>,
+    },
+    Database {
+        hostname: String,
+    },
+}
+
+#[allow(clippy::type_complexity)]
+fn save_design(
+    mut progress: ResMut<Progress>,
+    current_level: CurrentLevel,
+    nodes: Query<(
+        Entity,
+        &NodeType,
+        &NodeName,
+        &Transform,
+        &NodeConnections,
+        Option<&Hostname>,
+        Option<&Client>,
+        Option<&Server>,
+    )>,
+) {
+    let Some(level_id) = current_level.id() else {
+        return;
+    };
+
+    let nodes: Vec<_> = nodes.iter().collect();
+
+    let mut design = Design::default();
+
+    for (idx, (_, node_type, node_name, transform, connections, hostname, client, server)) in
+        nodes.iter().enumerate()
+    {
+        let hostname = hostname
+            .map(|hostname| hostname.0.clone())
+            .unwrap_or_default();
+
+        let component = match node_type {
+            // The Clients of the level are rebuilt from the level, so only the requests of
+            // Clients the player added are saved.
+            NodeType::Client => DesignComponent::Client {
+                request_configs: client
+                    .filter(|client| client.can_be_edited())
+                    .map(|client| client.request_configs.clone())
+                    .unwrap_or_default(),
+            },
+            NodeType::Server => DesignComponent::Server {
+                hostname,
+                endpoints: server
+                    .map(|server| server.endpoint_handlers.clone())
+                    .unwrap_or_default(),
+                middlewares: server
+                    .map(|server| server.middlewares.clone())
+                    .unwrap_or_default(),
+                health_checks: server
+                    .map(|server| server.health_checks.clone())
+                    .unwrap_or_default(),
+                rate_limit: server.and_then(|server| server.rate_limit.clone()),
+            },
+            NodeType::Database => DesignComponent::Database { hostname },
+        };
+
+        design.nodes.push(DesignNode {
+            name: node_name.0.clone(),
+            x: transform.translation.x,
+            y: transform.translation.y,
+            component,
+        });
+
+        // Each connection is stored on both of its nodes, so is only saved from its first.
+        for (other_node, _) in connections.iter() {
+            if let Some(other_idx) = nodes.iter().position(|(e, ..)| e == other_node) {
+                if idx < other_idx {
+                    design.connections.push((idx, other_idx));
+                }
+            }
+        }
+    }
+
+    progress
+        .levels
+        .entry(level_id.to_string())
+        .or_default()
+        .design = Some(design);
+    progress.save();
+}
+
+fn record_level_result(
+    mut progress: ResMut<Progress>,
+    current_level: CurrentLevel,
+    level_state: Res<LevelState>,
+) {
+    let Some(level_id) = current_level.id() else {
+        return;
+    };
+
+    progress.record_result(
+        level_id,
+        level_state.level_passed,
+        level_state.score.as_ref(),
+    );
+    progress.save();
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{level::ScoreWeights, message::Response};
+
+    use super::*;
+
+    fn score(nodes: usize, stars: Option<u8>) -> ScoreBreakdown {
+        ScoreBreakdown {
+            nodes,
+            handler_size: 0,
+            latency: Duration::ZERO,
+            messages: 0,
+            weights: ScoreWeights::default(),
+            stars,
+        }
+    }
+
+    #[test]
+    fn should_keep_best_score_and_stars_of_passing_solutions() {
+        let mut progress = Progress::default();
+
+        progress.record_result("hello-world", false, Some(&score(1, Some(0))));
+        assert!(!progress.is_completed("hello-world"));
+
+        progress.record_result("hello-world", true, Some(&score(5, Some(2))));
+        progress.record_result("hello-world", true, Some(&score(3, Some(1))));
+        progress.record_result("hello-world", true, Some(&score(9, Some(3))));
+
+        let level = &progress.levels["hello-world"];
+        assert!(level.completed);
+        assert_eq!(Some(300), level.best_score);
+        assert_eq!(Some(3), level.best_stars);
+    }
+
+    #[test]
+    fn should_read_progress_without_optional_fields() {
+        let progress: Progress =
+            serde_json::from_str(r#"{ "levels": { "hello-world": { "completed": true } } }"#)
+                .unwrap();
+
+        assert!(progress.is_completed("hello-world"));
+        assert!(!progress.is_completed("create"));
+        assert!(progress.levels["hello-world"].design.is_none());
+    }
+
+    #[test]
+    fn should_not_save_responses_of_requests() {
+        let mut request_config = RequestConfig::default();
+        request_config.response = Some(Response::healthy());
+
+        let saved = serde_json::to_value(DesignComponent::Client {
+            request_configs: vec![request_config],
+        })
+        .unwrap();
+
+        assert!(saved["Client"]["request_configs"][0]
+            .get("response")
+            .is_none());
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    game_state::{AppState, GameMode},
+    level::{CurrentLevel, LevelState, ScoreWeights},
+    metrics::Metrics,
+    node::{server::Server, NodeType},
+    trace::TraceStore,
+};
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            score_solution
+                .run_if(in_state(GameMode::Levels))
+                .in_schedule(OnEnter(AppState::SimulateFinish)),
+        );
+    }
+}
+
+/// What a solution used, and the score and stars it earned for it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreBreakdown {
+    pub nodes: usize,
+    // Non-whitespace characters of all handler code.
+    pub handler_size: usize,
+    // The total time from each Client request being sent until its response arrived.
+    pub latency: Duration,
+    pub messages: usize,
+    pub weights: ScoreWeights,
+    // Unset when the level has no star thresholds.
+    pub stars: Option<u8>,
+}
+
+impl ScoreBreakdown {
+    /// The name, amount and points of each part of the score.
+    pub fn components(&self) -> [(&'static str, String, f32); 4] {
+        let latency_ms = self.latency.as_secs_f32() * 1000.0;
+
+        [
+            (
+                "Nodes",
+                self.nodes.to_string(),
+                self.nodes as f32 * self.weights.per_node,
+            ),
+            (
+                "Handler code size",
+                format!("{} chars", self.handler_size),
+                self.handler_size as f32 * self.weights.per_handler_char,
+            ),
+            (
+                "End-to-end latency",
+                format!("{:.2}s", self.latency.as_secs_f32()),
+                latency_ms * self.weights.per_latency_ms,
+            ),
+            (
+                "Messages sent",
+                self.messages.to_string(),
+                self.messages as f32 * self.weights.per_message,
+            ),
+        ]
+    }
+
+    pub fn score(&self) -> u32 {
+        self.components()
+            .iter()
+            .map(|(_, _, points)| points)
+            .sum::<f32>()
+            .round() as u32
+    }
+}
+
+// A failed solution earns no stars, and a passing one earns a star for each threshold it is within.
+fn stars(score: u32, passed: bool, star_thresholds: &[u32; 3]) -> u8 {
+    if !passed {
+        return 0;
+    }
+
+    star_thresholds
+        .iter()
+        .filter(|threshold| score <= **threshold)
+        .count() as u8
+}
+
+pub fn score_solution(
+    mut level_state: ResMut<LevelState>,
+    current_level: CurrentLevel,
+    trace_store: Res<TraceStore>,
+    metrics: Res<Metrics>,
+    nodes: Query<&NodeType>,
+    servers: Query<&Server>,
+) {
+    let scoring = current_level
+        .get()
+        .and_then(|(_, level)| level.scoring.as_ref());
+
+    let mut score = ScoreBreakdown {
+        nodes: nodes.iter().count(),
+        handler_size: servers.iter().map(|server| server.handler_size()).sum(),
+        latency: trace_store
+            .roots()
+            .filter_map(|span| span.end.map(|end| end.saturating_sub(span.start)))
+            .sum(),
+        messages: metrics.messages_sent(),
+        weights: scoring.map(|scoring| scoring.weights).unwrap_or_default(),
+        stars: None,
+    };
+
+    score.stars = scoring.map(|scoring| {
+        stars(
+            score.score(),
+            level_state.level_passed,
+            &scoring.star_thresholds,
+        )
+    });
+
+    level_state.score = Some(score);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_weigh_each_part_of_the_score() {
+        let score = ScoreBreakdown {
+            nodes: 3,
+            handler_size: 120,
+            latency: Duration::from_millis(4500),
+            messages: 8,
+            weights: ScoreWeights::default(),
+            stars: None,
+        };
+
+        assert_eq!(300 + 120 + 450 + 80, score.score());
+    }
+
+    #[test]
+    fn should_award_a_star_for_each_threshold_met_by_a_passing_solution() {
+        let thresholds = [1500, 1000, 800];
+
+        assert_eq!(3, stars(800, true, &thresholds));
+        assert_eq!(2, stars(801, true, &thresholds));
+        assert_eq!(1, stars(1500, true, &thresholds));
+        assert_eq!(0, stars(1501, true, &thresholds));
+        assert_eq!(0, stars(800, false, &thresholds));
+    }
+}
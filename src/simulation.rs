@@ -1,12 +1,19 @@
+use std::time::Duration;
+
 use bevy::{
     prelude::{
         Commands, Component, DespawnRecursiveExt, Entity, IntoSystemAppConfigs, IntoSystemConfig,
-        IntoSystemConfigs, NextState, OnEnter, OnUpdate, Plugin, Query, ResMut, With,
+        IntoSystemConfigs, NextState, OnEnter, OnUpdate, Plugin, Query, Res, ResMut, Resource,
+        Time, With,
     },
     utils::HashSet,
 };
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 
 use crate::{
+    constraints::SystemConstraints,
+    cost::SystemCost,
     game_state::AppState,
     level::LevelState,
     message::MessageComponent,
@@ -22,13 +29,26 @@ pub struct SimulationPlugin;
 
 impl Plugin for SimulationPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<SimulationClock>();
+        app.init_resource::<SimulationRng>();
+
         app.add_systems(
-            (start::<Client>, start::<Server>, start::<Database>)
+            (
+                start::<Client>,
+                start::<Server>,
+                start::<Database>,
+                reset_simulation_clock,
+                reset_simulation_rng,
+            )
                 .in_schedule(OnEnter(AppState::Simulate)),
         );
 
+        app.add_system(tick_simulation_clock.in_set(OnUpdate(AppState::Simulate)));
+
         app.add_systems(
-            (client_system, server_system, database_system).in_set(OnUpdate(AppState::Simulate)),
+            (client_system, server_system, database_system)
+                .after(tick_simulation_clock)
+                .in_set(OnUpdate(AppState::Simulate)),
         );
 
         app.add_system(verify_solution.in_set(OnUpdate(AppState::Simulate)));
@@ -47,6 +67,41 @@ impl Plugin for SimulationPlugin {
     }
 }
 
+/// The time elapsed since the start of the current simulation.
+#[derive(Resource, Default)]
+pub struct SimulationClock {
+    elapsed: Duration,
+}
+
+impl SimulationClock {
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+fn reset_simulation_clock(mut simulation_clock: ResMut<SimulationClock>) {
+    simulation_clock.elapsed = Duration::ZERO;
+}
+
+pub fn tick_simulation_clock(mut simulation_clock: ResMut<SimulationClock>, time: Res<Time>) {
+    simulation_clock.elapsed += time.delta();
+}
+
+/// The source of randomness of a simulation, seeded the same way at the start of every simulation
+/// so that it plays out the same each time.
+#[derive(Resource)]
+pub struct SimulationRng(pub ChaCha8Rng);
+
+impl Default for SimulationRng {
+    fn default() -> Self {
+        Self(ChaCha8Rng::seed_from_u64(0))
+    }
+}
+
+fn reset_simulation_rng(mut simulation_rng: ResMut<SimulationRng>) {
+    *simulation_rng = SimulationRng::default();
+}
+
 fn start<T: Component + SystemNodeTrait>(mut query: Query<&mut T>) {
     for mut node in query.iter_mut() {
         node.start_simulation();
@@ -73,11 +128,15 @@ fn validate(
     hostnames: Query<&Hostname>,
     clients: Query<&Client>,
     servers: Query<&Server>,
+    system_cost: SystemCost,
+    system_constraints: SystemConstraints,
 ) {
     if HashSet::from_iter(hostnames.iter().map(|h| h.0.clone())).len() != hostnames.iter().len()
         || !hostnames.iter().all(|h| h.is_valid())
         || !clients.iter().all(|c| c.is_valid())
         || !servers.iter().all(|s| s.is_valid())
+        || !system_cost.is_infrastructure_within_budget()
+        || !system_constraints.are_met()
     {
         app_state.set(AppState::Edit);
         return;
@@ -88,9 +147,11 @@ fn validate(
 
 fn verify_solution(
     mut clients: Query<&mut Client>,
+    mut databases: Query<&mut Database>,
     message_query: Query<Entity, With<MessageComponent>>,
     mut app_state: ResMut<NextState<AppState>>,
     mut level_state: ResMut<LevelState>,
+    system_cost: SystemCost,
 ) {
     if !clients
         .iter()
@@ -112,6 +173,15 @@ fn verify_solution(
         }
     }
 
-    level_state.level_passed = passed;
+    // The documents are only checked once every Client has its responses, so that every write
+    // has been made.
+    for mut database in databases.iter_mut() {
+        if !database.verify() {
+            passed = false;
+        }
+    }
+
+    // Requests made during the simulation may have taken the system over budget.
+    level_state.level_passed = passed && system_cost.is_within_budget();
     app_state.set(AppState::SimulateFinish);
 }
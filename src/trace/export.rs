@@ -0,0 +1,373 @@
+use std::{collections::HashMap, fs, io, path::Path, str::FromStr};
+
+use bevy::prelude::Entity;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::{SpanOperation, SpanRecord};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::Display, strum::EnumIter)]
+pub enum TraceFormat {
+    #[strum(serialize = "OTLP")]
+    Otlp,
+    Jaeger,
+}
+
+impl TraceFormat {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            TraceFormat::Otlp => "traces.otlp.json",
+            TraceFormat::Jaeger => "traces.jaeger.json",
+        }
+    }
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "otlp" => Ok(TraceFormat::Otlp),
+            "jaeger" => Ok(TraceFormat::Jaeger),
+            _ => Err(format!(
+                "Unknown trace format `{s}`, expected `otlp` or `jaeger`."
+            )),
+        }
+    }
+}
+
+/// The node details included in exported spans.
+#[derive(Clone, Debug, Default)]
+pub struct ExportNode {
+    pub name: String,
+    pub hostname: Option<String>,
+}
+
+// Simulation time has no wall-clock start, so timestamps count from the Unix epoch as if the
+// simulation started then.
+pub fn export_traces(
+    spans: &[SpanRecord],
+    nodes: &HashMap<Entity, ExportNode>,
+    format: TraceFormat,
+) -> Value {
+    // Spans still in flight have no end, so are left out.
+    let spans: Vec<_> = spans.iter().filter(|span| span.end.is_some()).collect();
+
+    match format {
+        TraceFormat::Otlp => export_otlp(&spans, nodes),
+        TraceFormat::Jaeger => export_jaeger(&spans, nodes),
+    }
+}
+
+pub fn write_traces(path: &Path, traces: &Value) -> io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(traces)?)
+}
+
+fn trace_id_hex(id: Uuid) -> String {
+    id.simple().to_string()
+}
+
+// Span ids are 8 bytes in both formats, so only the first half of the UUID is used.
+fn span_id_hex(id: Uuid) -> String {
+    id.simple().to_string()[..16].to_string()
+}
+
+fn node(nodes: &HashMap<Entity, ExportNode>, entity: Entity) -> &ExportNode {
+    static UNKNOWN: ExportNode = ExportNode {
+        name: String::new(),
+        hostname: None,
+    };
+
+    nodes.get(&entity).unwrap_or(&UNKNOWN)
+}
+
+// The attributes of a span as key-value pairs, shared by both formats.
+fn span_attributes(
+    span: &SpanRecord,
+    nodes: &HashMap<Entity, ExportNode>,
+) -> Vec<(&'static str, Value)> {
+    let caller = node(nodes, span.caller);
+    let callee = node(nodes, span.callee);
+
+    let mut attributes = vec![
+        ("sender.name", Value::from(caller.name.clone())),
+        ("recipient.name", Value::from(callee.name.clone())),
+    ];
+
+    if let Some(hostname) = &caller.hostname {
+        attributes.push(("sender.hostname", Value::from(hostname.clone())));
+    }
+
+    if let Some(hostname) = &callee.hostname {
+        attributes.push(("recipient.hostname", Value::from(hostname.clone())));
+    }
+
+    match &span.operation {
+        SpanOperation::Http { url, method, path } => {
+            attributes.push(("server.address", Value::from(url.clone())));
+            attributes.push((
+                "http.request.method",
+                Value::from(method.to_string().to_ascii_uppercase()),
+            ));
+            attributes.push(("url.path", Value::from(path.clone())));
+        }
+        SpanOperation::Database { name, call } => {
+            attributes.push(("db.name", Value::from(name.clone())));
+            attributes.push(("db.operation", Value::from(*call)));
+        }
+    }
+
+    if let Some(status) = span.status {
+        attributes.push(("http.response.status_code", Value::from(status)));
+    }
+
+    attributes
+}
+
+fn export_otlp(spans: &[&SpanRecord], nodes: &HashMap<Entity, ExportNode>) -> Value {
+    // Spans are recorded by the node that made the call, so they are grouped by caller.
+    let mut callers: Vec<Entity> = vec![];
+    for span in spans {
+        if !callers.contains(&span.caller) {
+            callers.push(span.caller);
+        }
+    }
+
+    let resource_spans: Vec<_> = callers
+        .into_iter()
+        .map(|caller| {
+            let otlp_spans: Vec<_> = spans
+                .iter()
+                .filter(|span| span.caller == caller)
+                .map(|span| {
+                    let attributes: Vec<_> = span_attributes(span, nodes)
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let value = match value {
+                                Value::Number(n) => json!({ "intValue": n.to_string() }),
+                                value => json!({ "stringValue": value }),
+                            };
+                            json!({ "key": key, "value": value })
+                        })
+                        .collect();
+
+                    json!({
+                        "traceId": trace_id_hex(span.context.trace_id),
+                        "spanId": span_id_hex(span.context.span_id),
+                        "parentSpanId": span
+                            .context
+                            .parent_span_id
+                            .map(span_id_hex)
+                            .unwrap_or_default(),
+                        "name": span.operation.to_string(),
+                        // SPAN_KIND_CLIENT
+                        "kind": 3,
+                        "startTimeUnixNano": span.start.as_nanos().to_string(),
+                        "endTimeUnixNano": span.end.unwrap_or(span.start).as_nanos().to_string(),
+                        "attributes": attributes,
+                        // STATUS_CODE_ERROR or STATUS_CODE_OK
+                        "status": { "code": if span.is_error() { 2 } else { 1 } },
+                    })
+                })
+                .collect();
+
+            let caller = node(nodes, caller);
+
+            let mut resource_attributes =
+                vec![json!({ "key": "service.name", "value": { "stringValue": caller.name } })];
+
+            if let Some(hostname) = &caller.hostname {
+                resource_attributes
+                    .push(json!({ "key": "host.name", "value": { "stringValue": hostname } }));
+            }
+
+            json!({
+                "resource": { "attributes": resource_attributes },
+                "scopeSpans": [{
+                    "scope": { "name": env!("CARGO_PKG_NAME") },
+                    "spans": otlp_spans,
+                }],
+            })
+        })
+        .collect();
+
+    json!({ "resourceSpans": resource_spans })
+}
+
+fn export_jaeger(spans: &[&SpanRecord], nodes: &HashMap<Entity, ExportNode>) -> Value {
+    let mut trace_ids: Vec<Uuid> = vec![];
+    for span in spans {
+        if !trace_ids.contains(&span.context.trace_id) {
+            trace_ids.push(span.context.trace_id);
+        }
+    }
+
+    let data: Vec<_> = trace_ids
+        .into_iter()
+        .map(|trace_id| {
+            let trace_spans: Vec<_> = spans
+                .iter()
+                .filter(|span| span.context.trace_id == trace_id)
+                .collect();
+
+            let mut callers: Vec<Entity> = vec![];
+            for span in trace_spans.iter() {
+                if !callers.contains(&span.caller) {
+                    callers.push(span.caller);
+                }
+            }
+
+            let process_id = |caller: Entity| {
+                format!("p{}", callers.iter().position(|c| *c == caller).unwrap() + 1)
+            };
+
+            let jaeger_spans: Vec<_> = trace_spans
+                .iter()
+                .map(|span| {
+                    let tags: Vec<_> = span_attributes(span, nodes)
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let value_type = if value.is_number() { "int64" } else { "string" };
+                            json!({ "key": key, "type": value_type, "value": value })
+                        })
+                        .collect();
+
+                    let references: Vec<_> = span
+                        .context
+                        .parent_span_id
+                        .map(|parent_span_id| {
+                            json!({
+                                "refType": "CHILD_OF",
+                                "traceID": trace_id_hex(trace_id),
+                                "spanID": span_id_hex(parent_span_id),
+                            })
+                        })
+                        .into_iter()
+                        .collect();
+
+                    json!({
+                        "traceID": trace_id_hex(trace_id),
+                        "spanID": span_id_hex(span.context.span_id),
+                        "operationName": span.operation.to_string(),
+                        "references": references,
+                        "startTime": span.start.as_micros() as u64,
+                        "duration": span.duration(span.start).as_micros() as u64,
+                        "tags": tags,
+                        "logs": [],
+                        "processID": process_id(span.caller),
+                    })
+                })
+                .collect();
+
+            let processes: serde_json::Map<_, _> = callers
+                .iter()
+                .map(|caller| {
+                    let node = node(nodes, *caller);
+                    let tags: Vec<_> = node
+                        .hostname
+                        .iter()
+                        .map(|hostname| json!({ "key": "hostname", "type": "string", "value": hostname }))
+                        .collect();
+
+                    (
+                        process_id(*caller),
+                        json!({ "serviceName": node.name, "tags": tags }),
+                    )
+                })
+                .collect();
+
+            json!({
+                "traceID": trace_id_hex(trace_id),
+                "spans": jaeger_spans,
+                "processes": processes,
+            })
+        })
+        .collect();
+
+    json!({ "data": data })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{message::SpanContext, node::client::HttpMethod};
+
+    use super::*;
+
+    #[test]
+    fn should_export_parent_span_and_timing() {
+        let root = SpanContext::root(Uuid::new_v4());
+        let child = root.child();
+        let client = Entity::from_raw(0);
+        let server = Entity::from_raw(1);
+        let database = Entity::from_raw(2);
+
+        let spans = vec![
+            SpanRecord {
+                context: root,
+                caller: client,
+                callee: server,
+                operation: SpanOperation::Http {
+                    url: "server1".to_string(),
+                    method: HttpMethod::Get,
+                    path: "/users".to_string(),
+                },
+                endpoint: Some((HttpMethod::Get, "/users".to_string())),
+                start: Duration::from_millis(0),
+                end: Some(Duration::from_millis(1500)),
+                status: Some(200),
+            },
+            SpanRecord {
+                context: child,
+                caller: server,
+                callee: database,
+                operation: SpanOperation::Database {
+                    name: "users".to_string(),
+                    call: "findAll",
+                },
+                endpoint: None,
+                start: Duration::from_millis(500),
+                end: Some(Duration::from_millis(1000)),
+                status: None,
+            },
+        ];
+
+        let nodes = HashMap::from([
+            (
+                client,
+                ExportNode {
+                    name: "client1".to_string(),
+                    hostname: None,
+                },
+            ),
+            (
+                server,
+                ExportNode {
+                    name: "api".to_string(),
+                    hostname: Some("server1".to_string()),
+                },
+            ),
+        ]);
+
+        let otlp = export_traces(&spans, &nodes, TraceFormat::Otlp);
+        let db_span = &otlp["resourceSpans"][1]["scopeSpans"][0]["spans"][0];
+
+        assert_eq!(
+            json!([{ "key": "service.name", "value": { "stringValue": "api" } }, { "key": "host.name", "value": { "stringValue": "server1" } }]),
+            otlp["resourceSpans"][1]["resource"]["attributes"]
+        );
+        assert_eq!(json!(span_id_hex(root.span_id)), db_span["parentSpanId"]);
+        assert_eq!(json!("500000000"), db_span["startTimeUnixNano"]);
+
+        let jaeger = export_traces(&spans, &nodes, TraceFormat::Jaeger);
+        let root_span = &jaeger["data"][0]["spans"][0];
+
+        assert_eq!(json!(1500000), root_span["duration"]);
+        assert_eq!(json!("GET server1/users"), root_span["operationName"]);
+        assert_eq!(
+            json!(span_id_hex(root.span_id)),
+            jaeger["data"][0]["spans"][1]["references"][0]["spanID"]
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use std::{collections::HashMap, fmt::Display, path::PathBuf, time::Duration};
+
+use bevy::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    game_state::AppState,
+    message::{
+        DatabaseCallType, Message, MessageArrivedEvent, MessageArrivedEventSet, SendMessageEvent,
+        SpanContext,
+    },
+    node::{client::HttpMethod, server::Server, Hostname, NodeName, NodeType},
+    simulation::SimulationClock,
+};
+
+use self::export::{export_traces, write_traces, ExportNode, TraceFormat};
+
+pub mod export;
+
+pub struct TracePlugin;
+
+impl Plugin for TracePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TraceStore>();
+        app.add_system(clear_trace_store.in_schedule(OnEnter(AppState::Simulate)));
+        app.add_system(record_span_starts.run_if(on_event::<SendMessageEvent>()));
+        // Spans must be complete by the time the simulation finishes.
+        app.add_system(record_span_ends.in_set(MessageArrivedEventSet));
+        app.add_system(
+            export_finished_traces
+                .run_if(resource_exists::<TraceExportConfig>())
+                .in_schedule(OnEnter(AppState::SimulateFinish)),
+        );
+    }
+}
+
+/// Where to write the traces of every finished simulation, set with the
+/// `--export-traces <path>` and `--trace-format <otlp|jaeger>` command line arguments.
+#[derive(Resource, Debug, PartialEq)]
+pub struct TraceExportConfig {
+    pub path: PathBuf,
+    pub format: TraceFormat,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpanOperation {
+    Http {
+        url: String,
+        method: HttpMethod,
+        path: String,
+    },
+    Database {
+        name: String,
+        call: &'static str,
+    },
+}
+
+impl Display for SpanOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpanOperation::Http { url, method, path } => {
+                write!(f, "{} {url}{path}", method.to_string().to_ascii_uppercase())
+            }
+            SpanOperation::Database { name, call } => write!(f, "db.{call} {name}"),
+        }
+    }
+}
+
+/// A single request/response exchange between two nodes, timed in simulation time.
+#[derive(Clone, Debug)]
+pub struct SpanRecord {
+    pub context: SpanContext,
+    pub caller: Entity,
+    pub callee: Entity,
+    pub operation: SpanOperation,
+    // The method and path of the Server endpoint that handles the request, matched once when the
+    // request is sent so it is not matched again every time the span is shown.
+    pub endpoint: Option<(HttpMethod, String)>,
+    pub start: Duration,
+    // Unset until the response arrives back at the caller.
+    pub end: Option<Duration>,
+    // The response status, for HTTP requests.
+    pub status: Option<u16>,
+}
+
+impl SpanRecord {
+    pub fn is_error(&self) -> bool {
+        self.status.is_some_and(|status| status >= 400)
+    }
+
+    pub fn duration(&self, now: Duration) -> Duration {
+        self.end.unwrap_or(now).saturating_sub(self.start)
+    }
+}
+
+/// The spans of all traces of the current simulation, in the order they started.
+#[derive(Resource, Default)]
+pub struct TraceStore {
+    spans: Vec<SpanRecord>,
+}
+
+impl TraceStore {
+    pub fn spans(&self) -> &[SpanRecord] {
+        &self.spans
+    }
+
+    /// The root span of every trace, i.e. the requests sent by Clients.
+    pub fn roots(&self) -> impl Iterator<Item = &SpanRecord> {
+        self.spans
+            .iter()
+            .filter(|span| span.context.parent_span_id.is_none())
+    }
+
+    /// The spans of a trace in waterfall order, where each span is followed by its children,
+    /// along with how deeply each span is nested.
+    pub fn waterfall(&self, trace_id: Uuid) -> Vec<(usize, &SpanRecord)> {
+        let spans: Vec<_> = self
+            .spans
+            .iter()
+            .filter(|span| span.context.trace_id == trace_id)
+            .collect();
+
+        let mut waterfall = vec![];
+        let mut stack: Vec<_> = spans
+            .iter()
+            .rev()
+            .filter(|span| span.context.parent_span_id.is_none())
+            .map(|span| (0, *span))
+            .collect();
+
+        while let Some((depth, span)) = stack.pop() {
+            waterfall.push((depth, span));
+
+            stack.extend(
+                spans
+                    .iter()
+                    .rev()
+                    .filter(|child| child.context.parent_span_id == Some(span.context.span_id))
+                    .map(|child| (depth + 1, *child)),
+            );
+        }
+
+        waterfall
+    }
+
+    fn start(&mut self, span: SpanRecord) {
+        self.spans.push(span);
+    }
+
+    fn end(&mut self, span_id: Uuid, end: Duration, status: Option<u16>) {
+        if let Some(span) = self
+            .spans
+            .iter_mut()
+            .find(|span| span.context.span_id == span_id)
+        {
+            span.end = Some(end);
+            span.status = status;
+        }
+    }
+}
+
+fn database_call_name(call_type: &DatabaseCallType) -> &'static str {
+    match call_type {
+        DatabaseCallType::Save(_) => "save",
+        DatabaseCallType::FindOne(_) => "findOne",
+        DatabaseCallType::FindAll => "findAll",
+        DatabaseCallType::Contains(_) => "contains",
+        DatabaseCallType::Delete(_) => "delete",
+    }
+}
+
+/// The details of every node, for exporting spans.
+pub fn export_nodes(
+    nodes: &Query<(Entity, &NodeName, &NodeType, Option<&Hostname>)>,
+) -> HashMap<Entity, ExportNode> {
+    nodes
+        .iter()
+        .map(|(entity, node_name, node_type, hostname)| {
+            (
+                entity,
+                ExportNode {
+                    name: node_name.label(node_type, entity),
+                    hostname: hostname.map(|hostname| hostname.0.clone()),
+                },
+            )
+        })
+        .collect()
+}
+
+fn export_finished_traces(
+    trace_store: Res<TraceStore>,
+    config: Res<TraceExportConfig>,
+    nodes: Query<(Entity, &NodeName, &NodeType, Option<&Hostname>)>,
+) {
+    let traces = export_traces(trace_store.spans(), &export_nodes(&nodes), config.format);
+
+    match write_traces(&config.path, &traces) {
+        Ok(()) => info!("Exported traces to {}", config.path.display()),
+        Err(error) => error!(
+            "Failed to export traces to {}: {error}",
+            config.path.display()
+        ),
+    }
+}
+
+fn clear_trace_store(mut trace_store: ResMut<TraceStore>) {
+    trace_store.spans.clear();
+}
+
+fn record_span_starts(
+    mut events: EventReader<SendMessageEvent>,
+    mut trace_store: ResMut<TraceStore>,
+    simulation_clock: Res<SimulationClock>,
+    servers: Query<&Server>,
+) {
+    for event in events.iter() {
+        let operation = match &event.message {
+            Message::Request(request) => SpanOperation::Http {
+                url: request.url.clone(),
+                method: request.method,
+                path: request.path.clone(),
+            },
+            Message::DatabaseCall(database_call) => SpanOperation::Database {
+                name: database_call.name.clone(),
+                call: database_call_name(&database_call.call_type),
+            },
+            // Health checks are not part of any Client request.
+            Message::Response(_)
+            | Message::DatabaseAnswer(_)
+            | Message::DatabaseError(_)
+            | Message::HealthCheck => continue,
+        };
+
+        for recipient in event.recipients.iter() {
+            let endpoint = match &event.message {
+                Message::Request(request) => servers
+                    .get(*recipient)
+                    .ok()
+                    .and_then(|server| server.matched_endpoint(request.method, &request.path))
+                    .map(|endpoint| (endpoint.method, endpoint.path.clone())),
+                _ => None,
+            };
+
+            trace_store.start(SpanRecord {
+                context: event.span,
+                caller: event.sender,
+                callee: *recipient,
+                operation: operation.clone(),
+                endpoint,
+                start: simulation_clock.elapsed(),
+                end: None,
+                status: None,
+            });
+        }
+    }
+}
+
+fn record_span_ends(
+    mut events: EventReader<MessageArrivedEvent>,
+    mut trace_store: ResMut<TraceStore>,
+    simulation_clock: Res<SimulationClock>,
+) {
+    for MessageArrivedEvent(message) in events.iter() {
+        let status = match &message.message {
+            Message::Response(response) => Some(response.status),
+            Message::DatabaseAnswer(_) => None,
+            Message::DatabaseError(_) => Some(500),
+            Message::Request(_) | Message::DatabaseCall(_) | Message::HealthCheck => continue,
+        };
+
+        trace_store.end(message.span.span_id, simulation_clock.elapsed(), status);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn span(context: SpanContext, start: u64) -> SpanRecord {
+        SpanRecord {
+            context,
+            caller: Entity::from_raw(0),
+            callee: Entity::from_raw(1),
+            operation: SpanOperation::Database {
+                name: "users".to_string(),
+                call: "findAll",
+            },
+            endpoint: None,
+            start: Duration::from_secs(start),
+            end: None,
+            status: None,
+        }
+    }
+
+    #[test]
+    fn should_order_waterfall_with_children_after_their_parent() {
+        let root = SpanContext::root(Uuid::new_v4());
+        let first_child = root.child();
+        let grandchild = first_child.child();
+        let second_child = root.child();
+        let other_root = SpanContext::root(Uuid::new_v4());
+
+        let mut trace_store = TraceStore::default();
+        trace_store.start(span(root, 0));
+        trace_store.start(span(other_root, 1));
+        trace_store.start(span(first_child, 2));
+        trace_store.start(span(second_child, 3));
+        trace_store.start(span(grandchild, 4));
+
+        let waterfall: Vec<_> = trace_store
+            .waterfall(root.trace_id)
+            .into_iter()
+            .map(|(depth, span)| (depth, span.context.span_id))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (0, root.span_id),
+                (1, first_child.span_id),
+                (2, grandchild.span_id),
+                (1, second_child.span_id),
+            ],
+            waterfall
+        );
+    }
+}